@@ -0,0 +1,898 @@
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+use futures_util::{SinkExt, StreamExt};
+use log::{debug, error, info};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{broadcast, watch};
+use tokio_tungstenite::WebSocketStream;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::tungstenite::handshake::derive_accept_key;
+use tokio_tungstenite::tungstenite::protocol::Role;
+
+use crate::AppState;
+use crate::clip_catalog::ClipKind;
+use crate::mugi_schema;
+use crate::remote_trigger::{self, RemoteTrigger};
+
+// ヘッダーとボディの上限
+const MAX_REQUEST_BYTES: usize = 64 * 1024;
+// 接続してからリクエストを受け取り終わるまで待つ時間
+const REQUEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+// 別の席からの操作用のHTTP/WebSocketサーバーの設定
+// enabledでもtokenが空なら起動しない
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiSettings {
+    pub enabled: bool,
+    pub port: u16,
+    pub token: String,
+    // /api/ingestでMugiのコマンド(scored、endなど)も受け付ける
+    // 試合状況やシリーズの勝敗を動かせるので既定では外部トリガーだけにする
+    #[serde(default)]
+    pub allow_mugi_ingest: bool,
+}
+
+impl Default for ApiSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 12345,
+            token: String::new(),
+            allow_mugi_ingest: false,
+        }
+    }
+}
+
+// WebSocketで流すイベント
+#[derive(Serialize, Debug, Clone)]
+pub struct ApiEvent {
+    pub event: String,
+    pub payload: Value,
+}
+
+pub type ApiEvents = broadcast::Sender<ApiEvent>;
+
+pub fn publish<T: Serialize>(events: &ApiEvents, event: &str, payload: &T) {
+    let payload = match serde_json::to_value(payload) {
+        Ok(payload) => payload,
+        Err(e) => {
+            error!("Failed to serialize {} event: {}", event, e);
+            return;
+        }
+    };
+    // 接続中のクライアントがいなければ捨てる
+    let _ = events.send(ApiEvent {
+        event: event.to_string(),
+        payload,
+    });
+}
+
+// 一度に再生できる直近のクリップ数
+const MAX_LATEST_CLIPS: usize = 20;
+
+// Companion/Stream DeckやOSCから叩く単発の操作
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RemoteAction {
+    // 直近n個のクリップを再生
+    PlayLatest(usize),
+    Clip,
+    Stop,
+}
+
+impl RemoteAction {
+    // /play/latest /play/last/3 /clip /stop
+    pub fn from_path(path: &str) -> Option<Self> {
+        let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+        match segments.as_slice() {
+            ["play", "latest"] => Some(Self::PlayLatest(1)),
+            ["play", "last", n] => match n.parse() {
+                Ok(n @ 1..=MAX_LATEST_CLIPS) => Some(Self::PlayLatest(n)),
+                _ => None,
+            },
+            ["clip"] => Some(Self::Clip),
+            ["stop"] => Some(Self::Stop),
+            _ => None,
+        }
+    }
+}
+
+pub async fn perform(state: &AppState, action: RemoteAction) -> Result<Value, String> {
+    match action {
+        RemoteAction::PlayLatest(n) => {
+            let paths: Vec<PathBuf> = {
+                let catalog = state.clip_catalog.lock().unwrap();
+                catalog.latest(n).iter().map(|c| c.path.clone()).collect()
+            };
+            if paths.is_empty() {
+                return Err("再生するクリップがありません".to_string());
+            }
+            // ボタンは押しっぱなしにならないよう再生の開始を待たずに返す
+            let count = crate::spawn_play_paths(state, paths).await?;
+            Ok(json!({ "played": count }))
+        }
+        RemoteAction::Clip => {
+            crate::save_clip(state, ClipKind::Manual)?;
+            Ok(json!({ "saving": true }))
+        }
+        RemoteAction::Stop => {
+            crate::stop_current_playback(state).await?;
+            Ok(json!({ "stopped": true }))
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PlayRequest {
+    #[serde(default)]
+    clip_ids: Vec<u64>,
+    #[serde(default)]
+    paths: Vec<PathBuf>,
+}
+
+struct Request {
+    method: String,
+    path: String,
+    query: Option<String>,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Request {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    fn query_param(&self, name: &str) -> Option<&str> {
+        self.query.as_deref()?.split('&').find_map(|pair| {
+            let (key, value) = pair.split_once('=')?;
+            (key == name).then_some(value)
+        })
+    }
+
+    // Authorization: Bearer <token>。ブラウザのWebSocketはヘッダーを付けられないので?token=も受け付ける
+    fn token(&self) -> Option<&str> {
+        self.header("authorization")
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .or_else(|| self.query_param("token"))
+    }
+}
+
+struct Response {
+    status: u16,
+    body: Value,
+}
+
+impl Response {
+    fn ok(body: impl Serialize) -> Self {
+        match serde_json::to_value(body) {
+            Ok(body) => Self { status: 200, body },
+            Err(e) => Self::error(500, &e.to_string()),
+        }
+    }
+
+    fn error(status: u16, message: &str) -> Self {
+        Self {
+            status,
+            body: json!({ "error": message }),
+        }
+    }
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        204 => "No Content",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        413 => "Payload Too Large",
+        _ => "Internal Server Error",
+    }
+}
+
+// トークンの比較で一致した長さが漏れないようにする
+fn token_matches(given: &str, expected: &str) -> bool {
+    if given.len() != expected.len() {
+        return false;
+    }
+    given
+        .bytes()
+        .zip(expected.bytes())
+        .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+        == 0
+}
+
+pub struct ApiServer {
+    pub addr: SocketAddr,
+    pub handle: tokio::task::JoinHandle<()>,
+    // 接続中のクライアントを切断する合図。落としても切断される
+    shutdown: watch::Sender<bool>,
+}
+
+impl ApiServer {
+    // 待ち受けを止め、接続中のクライアント(WebSocketを含む)も切断する
+    pub fn stop(self) {
+        let _ = self.shutdown.send(true);
+        self.handle.abort();
+    }
+}
+
+// 待ち受けを開始する。ポートが使えなければエラーを返す
+pub async fn start(settings: &ApiSettings, state: AppState) -> Result<ApiServer, String> {
+    if settings.token.is_empty() {
+        return Err("トークンを設定してください".to_string());
+    }
+    let listener = TcpListener::bind(("0.0.0.0", settings.port))
+        .await
+        .map_err(|e| format!("Failed to bind port {}: {}", settings.port, e))?;
+    let addr = listener.local_addr().map_err(|e| e.to_string())?;
+    info!("Remote API listening on {}", addr);
+
+    let token = settings.token.clone();
+    let (shutdown, shutdown_rx) = watch::channel(false);
+    let handle = tokio::spawn(async move {
+        loop {
+            let (stream, peer) = match listener.accept().await {
+                Ok(conn) => conn,
+                Err(e) => {
+                    error!("Failed to accept API connection: {}", e);
+                    continue;
+                }
+            };
+            let state = state.clone();
+            let token = token.clone();
+            let mut shutdown = shutdown_rx.clone();
+            tokio::spawn(async move {
+                tokio::select! {
+                    res = handle_connection(stream, &state, &token) => {
+                        if let Err(e) = res {
+                            debug!("API connection from {} closed: {}", peer, e);
+                        }
+                    }
+                    _ = shutdown.changed() => debug!("API connection from {} shut down", peer),
+                }
+            });
+        }
+    });
+    Ok(ApiServer {
+        addr,
+        handle,
+        shutdown,
+    })
+}
+
+async fn read_request(stream: &mut TcpStream) -> Result<Request, String> {
+    let mut buf = Vec::new();
+    let mut chunk = [0; 4096];
+    loop {
+        let n = stream.read(&mut chunk).await.map_err(|e| e.to_string())?;
+        if n == 0 {
+            return Err("connection closed".to_string());
+        }
+        buf.extend_from_slice(&chunk[..n]);
+
+        let mut headers = [httparse::EMPTY_HEADER; 32];
+        let mut req = httparse::Request::new(&mut headers);
+        let header_len = match req.parse(&buf).map_err(|e| e.to_string())? {
+            httparse::Status::Complete(len) => len,
+            httparse::Status::Partial if buf.len() < MAX_REQUEST_BYTES => continue,
+            httparse::Status::Partial => return Err("request too large".to_string()),
+        };
+        let (path, query) = match req.path.unwrap_or("/").split_once('?') {
+            Some((path, query)) => (path.to_string(), Some(query.to_string())),
+            None => (req.path.unwrap_or("/").to_string(), None),
+        };
+        let headers: Vec<(String, String)> = req
+            .headers
+            .iter()
+            .map(|h| {
+                (
+                    h.name.to_string(),
+                    String::from_utf8_lossy(h.value).into_owned(),
+                )
+            })
+            .collect();
+        let mut request = Request {
+            method: req.method.unwrap_or("GET").to_string(),
+            path,
+            query,
+            headers,
+            body: buf[header_len..].to_vec(),
+        };
+
+        let content_length: usize = request
+            .header("content-length")
+            .and_then(|v| v.trim().parse().ok())
+            .unwrap_or(0);
+        if header_len + content_length > MAX_REQUEST_BYTES {
+            return Err("request too large".to_string());
+        }
+        while request.body.len() < content_length {
+            let n = stream.read(&mut chunk).await.map_err(|e| e.to_string())?;
+            if n == 0 {
+                return Err("connection closed".to_string());
+            }
+            request.body.extend_from_slice(&chunk[..n]);
+        }
+        request.body.truncate(content_length);
+        return Ok(request);
+    }
+}
+
+async fn write_response(stream: &mut TcpStream, response: Response) -> Result<(), String> {
+    let body = if response.status == 204 {
+        String::new()
+    } else {
+        response.body.to_string()
+    };
+    let head = format!(
+        "HTTP/1.1 {} {}\r\n\
+         Content-Type: application/json\r\n\
+         Content-Length: {}\r\n\
+         Access-Control-Allow-Origin: *\r\n\
+         Access-Control-Allow-Headers: Authorization, Content-Type\r\n\
+         Access-Control-Allow-Methods: GET, POST, OPTIONS\r\n\
+         Connection: close\r\n\r\n",
+        response.status,
+        reason(response.status),
+        body.len()
+    );
+    stream
+        .write_all(head.as_bytes())
+        .await
+        .map_err(|e| e.to_string())?;
+    stream
+        .write_all(body.as_bytes())
+        .await
+        .map_err(|e| e.to_string())?;
+    stream.shutdown().await.map_err(|e| e.to_string())
+}
+
+async fn handle_connection(
+    mut stream: TcpStream,
+    state: &AppState,
+    token: &str,
+) -> Result<(), String> {
+    // 何も送らない接続がタスクを持ち続けないようにする
+    let request = tokio::time::timeout(REQUEST_TIMEOUT, read_request(&mut stream))
+        .await
+        .map_err(|_| "request timed out".to_string())??;
+    // CORSのプリフライトは認証無しで返す
+    if request.method == "OPTIONS" {
+        let response = Response {
+            status: 204,
+            body: Value::Null,
+        };
+        return write_response(&mut stream, response).await;
+    }
+    if !request.token().is_some_and(|t| token_matches(t, token)) {
+        return write_response(&mut stream, Response::error(401, "invalid token")).await;
+    }
+
+    let is_upgrade = request
+        .header("upgrade")
+        .is_some_and(|v| v.eq_ignore_ascii_case("websocket"));
+    if request.path == "/api/events" && is_upgrade {
+        let events = state.api_events.subscribe();
+        let Some(ws) = accept_websocket(stream, &request).await? else {
+            return Ok(());
+        };
+        return stream_broadcast(ws, events, |event| {
+            serde_json::to_string(&event).map_err(|e| e.to_string())
+        })
+        .await;
+    }
+    // 受信したMugiのデータグラムをそのまま流す(転送設定でwebsocketが有効なとき)
+    if request.path == "/api/mugi" && is_upgrade {
+        let packets = state.mugi_raw.subscribe();
+        let Some(ws) = accept_websocket(stream, &request).await? else {
+            return Ok(());
+        };
+        return stream_broadcast(ws, packets, Ok).await;
+    }
+    if request.path == "/api/ingest" && is_upgrade {
+        let Some(ws) = accept_websocket(stream, &request).await? else {
+            return Ok(());
+        };
+        return ingest_socket(ws, state).await;
+    }
+
+    let response = route(&request, state).await;
+    write_response(&mut stream, response).await
+}
+
+async fn route(request: &Request, state: &AppState) -> Response {
+    // ボタン用の短いURL。Companionのプリセットでも使えるようGETも受け付ける
+    if let Some(action) = RemoteAction::from_path(&request.path) {
+        if !matches!(request.method.as_str(), "GET" | "POST") {
+            return Response::error(405, "method not allowed");
+        }
+        return match perform(state, action).await {
+            Ok(body) => Response::ok(body),
+            Err(e) => Response::error(409, &e),
+        };
+    }
+    match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/api/status") => Response::ok(crate::system_status(state)),
+        ("GET", "/api/clips") => Response::ok(state.clip_catalog.lock().unwrap().clips()),
+        ("POST", "/api/play") => {
+            let play: PlayRequest = match serde_json::from_slice(&request.body) {
+                Ok(play) => play,
+                Err(e) => return Response::error(400, &e.to_string()),
+            };
+            // Tauriコマンドと同じく、空の再生リストでOBSを操作しない
+            if play.paths.is_empty() && play.clip_ids.is_empty() {
+                return Response::error(400, "再生する動画がありません");
+            }
+            let mut paths = Vec::new();
+            {
+                let catalog = state.clip_catalog.lock().unwrap();
+                // OBSにホスト上の任意のファイルを開かせないよう、カタログにあるクリップだけ再生する
+                for path in play.paths {
+                    match catalog.find_by_path(&path) {
+                        Some(clip) => paths.push(clip.path.clone()),
+                        None => {
+                            let message = format!("clip {} not found", path.display());
+                            return Response::error(404, &message);
+                        }
+                    }
+                }
+                for id in play.clip_ids {
+                    match catalog.get(id) {
+                        Some(clip) => paths.push(clip.path.clone()),
+                        None => return Response::error(404, &format!("clip {id} not found")),
+                    }
+                }
+            }
+            match crate::play_paths(state, paths).await {
+                Ok(count) => Response::ok(json!({ "played": count })),
+                Err(e) => Response::error(409, &e),
+            }
+        }
+        // 1件のメッセージか、その配列を受け付ける
+        ("POST", "/api/ingest") => {
+            let messages = match serde_json::from_slice(&request.body) {
+                Ok(Value::Array(messages)) => messages,
+                Ok(msg) => vec![msg],
+                Err(e) => return Response::error(400, &e.to_string()),
+            };
+            match ingest(state, messages).await {
+                Ok(count) => Response::ok(json!({ "accepted": count })),
+                Err(response) => response,
+            }
+        }
+        ("POST", "/api/stop") => match perform(state, RemoteAction::Stop).await {
+            Ok(body) => Response::ok(body),
+            Err(e) => Response::error(409, &e),
+        },
+        ("POST", "/api/clip") => match perform(state, RemoteAction::Clip).await {
+            Ok(body) => Response::ok(body),
+            Err(e) => Response::error(409, &e),
+        },
+        (
+            _,
+            "/api/status" | "/api/clips" | "/api/play" | "/api/stop" | "/api/clip" | "/api/ingest",
+        ) => Response::error(405, "method not allowed"),
+        _ => Response::error(404, "not found"),
+    }
+}
+
+// ハンドシェイクに応答してWebSocketに切り替える。キーが無ければ400を返してNone
+async fn accept_websocket(
+    mut stream: TcpStream,
+    request: &Request,
+) -> Result<Option<WebSocketStream<TcpStream>>, String> {
+    let Some(key) = request.header("sec-websocket-key") else {
+        write_response(&mut stream, Response::error(400, "missing websocket key")).await?;
+        return Ok(None);
+    };
+    let head = format!(
+        "HTTP/1.1 101 Switching Protocols\r\n\
+         Upgrade: websocket\r\n\
+         Connection: Upgrade\r\n\
+         Sec-WebSocket-Accept: {}\r\n\r\n",
+        derive_accept_key(key.as_bytes())
+    );
+    stream
+        .write_all(head.as_bytes())
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let ws = WebSocketStream::from_raw_socket(stream, Role::Server, None).await;
+    Ok(Some(ws))
+}
+
+async fn stream_broadcast<T: Clone>(
+    ws: WebSocketStream<TcpStream>,
+    mut rx: broadcast::Receiver<T>,
+    encode: impl Fn(T) -> Result<String, String>,
+) -> Result<(), String> {
+    let (mut sink, mut incoming) = ws.split();
+    loop {
+        tokio::select! {
+            item = rx.recv() => {
+                let item = match item {
+                    Ok(item) => item,
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        debug!("API client lagged {} events", n);
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return Ok(()),
+                };
+                let text = encode(item)?;
+                sink.send(Message::text(text)).await.map_err(|e| e.to_string())?;
+            }
+            msg = incoming.next() => match msg {
+                Some(Ok(Message::Close(_))) | None => return Ok(()),
+                Some(Ok(_)) => {}
+                Some(Err(e)) => return Err(e.to_string()),
+            }
+        }
+    }
+}
+
+// 取り込むメッセージ。トリガーは専用のチャネルに、Mugiのコマンドは受信したUDPと同じチャネルに流す
+enum Ingested {
+    Trigger(RemoteTrigger),
+    Mugi(String),
+}
+
+// Mugi形式のメッセージを検証してそれぞれのチャネルに流す
+// 1つでも読めないメッセージがあれば何も流さない
+async fn ingest(state: &AppState, messages: Vec<Value>) -> Result<usize, Response> {
+    let not_running = || Response::error(409, "システムが起動していません");
+    let (Some(mugi_tx), Some(trigger_tx)) = (
+        state.mugi_tx.lock().unwrap().clone(),
+        state.trigger_tx.lock().unwrap().clone(),
+    ) else {
+        return Err(not_running());
+    };
+    let mut items = Vec::new();
+    for msg in messages {
+        let text = msg.to_string();
+        let item = match remote_trigger::parse_trigger(&text) {
+            Ok(Some(trigger)) => Ok(Ingested::Trigger(trigger)),
+            Ok(None) => mugi_schema::parse_cmd(&text).map(|_| Ingested::Mugi(text.clone())),
+            Err(e) => Err(e),
+        };
+        if matches!(item, Ok(Ingested::Mugi(_)))
+            && !state.api_settings.read().unwrap().allow_mugi_ingest
+        {
+            return Err(Response::error(
+                403,
+                &format!("Mugi commands are not allowed: {}", text),
+            ));
+        }
+        match item {
+            Ok(item) => items.push(item),
+            Err(e) => {
+                return Err(Response::error(
+                    400,
+                    &format!("invalid message {}: {}", text, e),
+                ));
+            }
+        }
+    }
+    let count = items.len();
+    for item in items {
+        let sent = match item {
+            Ingested::Trigger(trigger) => trigger_tx.send(trigger).await.is_ok(),
+            Ingested::Mugi(text) => mugi_tx.send(text).await.is_ok(),
+        };
+        if !sent {
+            return Err(not_running());
+        }
+    }
+    Ok(count)
+}
+
+// 届いたテキストメッセージを1件ずつ取り込み、結果を返信する
+async fn ingest_socket(ws: WebSocketStream<TcpStream>, state: &AppState) -> Result<(), String> {
+    let (mut sink, mut incoming) = ws.split();
+    while let Some(msg) = incoming.next().await {
+        let text = match msg.map_err(|e| e.to_string())? {
+            Message::Text(text) => text,
+            Message::Close(_) => return Ok(()),
+            _ => continue,
+        };
+        let reply = match serde_json::from_str::<Value>(&text) {
+            Ok(msg) => match ingest(state, vec![msg]).await {
+                Ok(count) => json!({ "accepted": count }),
+                Err(response) => response.body,
+            },
+            Err(e) => json!({ "error": e.to_string() }),
+        };
+        sink.send(Message::text(reply.to_string()))
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    async fn request(addr: SocketAddr, raw: &str) -> String {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(raw.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn test_http_api() {
+        let state = AppState::new();
+        let settings = ApiSettings {
+            enabled: true,
+            port: 0,
+            token: "secret".to_string(),
+            ..Default::default()
+        };
+        let server = start(&settings, state.clone()).await.unwrap();
+        let addr = SocketAddr::from(([127, 0, 0, 1], server.addr.port()));
+
+        let res = request(addr, "GET /api/clips HTTP/1.1\r\nHost: x\r\n\r\n").await;
+        assert!(res.starts_with("HTTP/1.1 401"), "{res}");
+
+        let res = request(
+            addr,
+            "GET /api/clips HTTP/1.1\r\nHost: x\r\nAuthorization: Bearer secret\r\n\r\n",
+        )
+        .await;
+        assert!(res.starts_with("HTTP/1.1 200"), "{res}");
+        assert!(res.ends_with("\r\n\r\n[]"), "{res}");
+
+        // 空の指定は再生リストを作らずに拒否する
+        let body = r#"{"paths":[],"clipIds":[]}"#;
+        let empty = format!(
+            "POST /api/play?token=secret HTTP/1.1\r\nHost: x\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        let res = request(addr, &empty).await;
+        assert!(res.starts_with("HTTP/1.1 400"), "{res}");
+
+        // カタログに無いファイルは再生させない
+        let body = r#"{"paths":["a.mkv"]}"#;
+        let play = format!(
+            "POST /api/play?token=secret HTTP/1.1\r\nHost: x\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        let res = request(addr, &play).await;
+        assert!(res.starts_with("HTTP/1.1 404"), "{res}");
+
+        let res = request(
+            addr,
+            "GET /play/last/3?token=secret HTTP/1.1\r\nHost: x\r\n\r\n",
+        )
+        .await;
+        assert!(res.starts_with("HTTP/1.1 409"), "{res}");
+        assert!(res.contains("再生するクリップがありません"), "{res}");
+
+        // システムが起動していなければ再生できない
+        state
+            .clip_catalog
+            .lock()
+            .unwrap()
+            .attach(PathBuf::from("a.mkv"));
+        let res = request(addr, &play).await;
+        assert!(res.starts_with("HTTP/1.1 409"), "{res}");
+        server.handle.abort();
+    }
+
+    #[tokio::test]
+    async fn test_play_latest_trimmed_clips() {
+        use crate::backend::Backends;
+        use crate::clip_catalog::Trim;
+        use crate::obs::Obs;
+        use crate::obs_mock::MockObs;
+        use std::sync::Arc;
+
+        let mock = MockObs::start().await;
+        let mut obs = Obs::new();
+        obs.connect("127.0.0.1", mock.port, None).await.unwrap();
+        obs.init_vlc_source().await.unwrap();
+        let obs = Arc::new(obs);
+        let state = AppState::new();
+        *state.backends.lock().unwrap() = Some(Backends {
+            capture: Vec::new(),
+            playback: obs.clone(),
+            overlay: obs,
+        });
+        {
+            let mut catalog = state.clip_catalog.lock().unwrap();
+            for name in ["a.mkv", "b.mkv"] {
+                let clip = catalog.attach(PathBuf::from(name));
+                let trim = Trim {
+                    in_sec: Some(1.0),
+                    out_sec: Some(2.0),
+                };
+                catalog.set_trim(clip.id, trim).unwrap();
+            }
+        }
+        let settings = ApiSettings {
+            enabled: true,
+            port: 0,
+            token: "secret".to_string(),
+            ..Default::default()
+        };
+        let server = start(&settings, state.clone()).await.unwrap();
+        let addr = SocketAddr::from(([127, 0, 0, 1], server.addr.port()));
+
+        // トリム付きでもプレイリストの終わりを待たずに返る
+        let res = tokio::time::timeout(
+            std::time::Duration::from_secs(2),
+            request(
+                addr,
+                "GET /play/last/2?token=secret HTTP/1.1\r\nHost: x\r\n\r\n",
+            ),
+        )
+        .await
+        .unwrap();
+        assert!(res.ends_with(r#"{"played":2}"#), "{res}");
+
+        // 停止は再生の開始を待ってから処理され、次のクリップに切り替わらない
+        let res = request(addr, "GET /stop?token=secret HTTP/1.1\r\nHost: x\r\n\r\n").await;
+        assert!(res.starts_with("HTTP/1.1 200"), "{res}");
+        assert_eq!(mock.requests_of("SetMediaInputCursor").len(), 1);
+        mock.set_media("OBS_MEDIA_STATE_PLAYING", Some(2000.0));
+        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+        let playlists = mock.requests_of("SetInputSettings");
+        assert_eq!(playlists.len(), 1);
+        assert_eq!(
+            playlists[0]["inputSettings"]["playlist"][0]["value"],
+            "a.mkv"
+        );
+        let actions = mock.requests_of("TriggerMediaInputAction");
+        assert_eq!(
+            actions.last().unwrap()["mediaAction"],
+            "OBS_WEBSOCKET_MEDIA_INPUT_ACTION_STOP"
+        );
+        server.handle.abort();
+    }
+
+    #[test]
+    fn test_remote_action_from_path() {
+        assert_eq!(
+            RemoteAction::from_path("/play/latest"),
+            Some(RemoteAction::PlayLatest(1))
+        );
+        assert_eq!(
+            RemoteAction::from_path("/play/last/3/"),
+            Some(RemoteAction::PlayLatest(3))
+        );
+        assert_eq!(RemoteAction::from_path("/play/last/0"), None);
+        assert_eq!(RemoteAction::from_path("/play/last/x"), None);
+        assert_eq!(RemoteAction::from_path("/clip"), Some(RemoteAction::Clip));
+        assert_eq!(RemoteAction::from_path("/stop"), Some(RemoteAction::Stop));
+        assert_eq!(RemoteAction::from_path("/api/clips"), None);
+    }
+
+    #[tokio::test]
+    async fn test_ingest() {
+        let state = AppState::new();
+        let (tx, mut rx) = tokio::sync::mpsc::channel(8);
+        *state.mugi_tx.lock().unwrap() = Some(tx);
+        let (tx, mut triggers) = tokio::sync::mpsc::channel(8);
+        *state.trigger_tx.lock().unwrap() = Some(tx);
+        let settings = ApiSettings {
+            enabled: true,
+            port: 0,
+            token: "secret".to_string(),
+            ..Default::default()
+        };
+        let server = start(&settings, state.clone()).await.unwrap();
+        let addr = SocketAddr::from(([127, 0, 0, 1], server.addr.port()));
+        let post = |body: &str| {
+            format!(
+                "POST /api/ingest?token=secret HTTP/1.1\r\nHost: x\r\nContent-Length: {}\r\n\r\n{}",
+                body.len(),
+                body
+            )
+        };
+
+        let body =
+            r#"[{"cmd":"clip","data":{"label":"caster callout"}},{"cmd":"epicSave","data":null}]"#;
+        // 既定ではMugiのコマンドを受け付けず、トリガーも流さない
+        let res = request(addr, &post(body)).await;
+        assert!(res.starts_with("HTTP/1.1 403"), "{res}");
+        assert!(rx.try_recv().is_err());
+        assert!(triggers.try_recv().is_err());
+
+        state.api_settings.write().unwrap().allow_mugi_ingest = true;
+        let res = request(addr, &post(body)).await;
+        assert!(res.ends_with(r#"{"accepted":2}"#), "{res}");
+        // clipはMugiのチャネルではなくトリガーとして流す
+        assert_eq!(
+            triggers.recv().await.unwrap(),
+            RemoteTrigger::Clip(remote_trigger::ClipRequest {
+                label: Some("caster callout".to_string()),
+            })
+        );
+        assert_eq!(
+            rx.recv().await.unwrap(),
+            r#"{"cmd":"epicSave","data":null}"#
+        );
+
+        // 読めないメッセージが混ざっていれば何も流さない
+        let body = r#"[{"cmd":"scored"},{"data":1}]"#;
+        let res = request(addr, &post(body)).await;
+        assert!(res.starts_with("HTTP/1.1 400"), "{res}");
+        assert!(rx.try_recv().is_err());
+        assert!(triggers.try_recv().is_err());
+
+        let url = format!(
+            "ws://127.0.0.1:{}/api/ingest?token=secret",
+            server.addr.port()
+        );
+        let (mut ws, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+        ws.send(Message::text(r#"{"cmd":"clip","data":{}}"#))
+            .await
+            .unwrap();
+        let reply = ws.next().await.unwrap().unwrap();
+        assert_eq!(reply.to_text().unwrap(), r#"{"accepted":1}"#);
+        assert_eq!(
+            triggers.recv().await.unwrap(),
+            RemoteTrigger::Clip(remote_trigger::ClipRequest::default())
+        );
+        server.handle.abort();
+    }
+
+    #[tokio::test]
+    async fn test_event_stream() {
+        let state = AppState::new();
+        let settings = ApiSettings {
+            enabled: true,
+            port: 0,
+            token: "secret".to_string(),
+            ..Default::default()
+        };
+        let server = start(&settings, state.clone()).await.unwrap();
+        let url = format!(
+            "ws://127.0.0.1:{}/api/events?token=secret",
+            server.addr.port()
+        );
+        let (mut ws, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+
+        // 購読が始まるまで送り直す
+        let msg = loop {
+            publish(&state.api_events, "clip_saved", &json!({ "id": 1 }));
+            match tokio::time::timeout(std::time::Duration::from_millis(100), ws.next()).await {
+                Ok(msg) => break msg.unwrap().unwrap(),
+                Err(_) => continue,
+            }
+        };
+        let event: Value = serde_json::from_str(msg.to_text().unwrap()).unwrap();
+        assert_eq!(
+            event,
+            json!({ "event": "clip_saved", "payload": { "id": 1 } })
+        );
+
+        // 止めると接続中のWebSocketも切断される
+        server.stop();
+        let closed = tokio::time::timeout(std::time::Duration::from_secs(2), async {
+            while let Some(Ok(msg)) = ws.next().await {
+                if msg.is_close() {
+                    break;
+                }
+            }
+        })
+        .await;
+        assert!(closed.is_ok());
+    }
+}
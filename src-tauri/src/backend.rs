@@ -0,0 +1,179 @@
+use std::future::Future;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use serde::Serialize;
+use serde_json::Value;
+use tokio::sync::mpsc::Sender;
+
+use crate::obs::{Obs, PlaybackItem};
+
+// トレイトオブジェクトで扱うためFutureをBoxに包んで返す
+pub type BackendFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, String>> + Send + 'a>>;
+
+// どの録画先(アングル)で保存されたクリップか
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct SavedClip {
+    pub angle: String,
+    pub path: PathBuf,
+}
+
+// 録画側: リプレイを保存し、保存されたクリップのパスを通知する
+pub trait ClipBackend: Send + Sync {
+    fn save_replay(&self) -> BackendFuture<'_, ()>;
+    // 保存されたクリップのパスをtxに流し続ける
+    fn listen_saved_clips(&self, tx: Sender<PathBuf>) -> BackendFuture<'_, ()>;
+    // 保存先にあるクリップ。アプリを起動する前に保存されたものも含む
+    fn list_saved_clips(&self) -> BackendFuture<'_, Vec<PathBuf>>;
+}
+
+// 再生側: クリップをin/out点付きで順番に再生する
+pub trait PlaybackBackend: Send + Sync {
+    fn play_playlist<'a>(&'a self, items: &'a [PlaybackItem]) -> BackendFuture<'a, ()>;
+    fn stop(&self) -> BackendFuture<'_, ()>;
+}
+
+// 表示側: スコアボードなどのソースに値を流し込む
+pub trait OverlayBackend: Send + Sync {
+    fn set_input_settings<'a>(
+        &'a self,
+        input: &'a str,
+        settings: &'a Value,
+    ) -> BackendFuture<'a, ()>;
+}
+
+impl ClipBackend for Obs {
+    fn save_replay(&self) -> BackendFuture<'_, ()> {
+        Box::pin(self.save_replay_buffer())
+    }
+
+    fn listen_saved_clips(&self, tx: Sender<PathBuf>) -> BackendFuture<'_, ()> {
+        Box::pin(self.set_event_listener(tx))
+    }
+
+    fn list_saved_clips(&self) -> BackendFuture<'_, Vec<PathBuf>> {
+        Box::pin(self.list_replays())
+    }
+}
+
+impl PlaybackBackend for Obs {
+    fn play_playlist<'a>(&'a self, items: &'a [PlaybackItem]) -> BackendFuture<'a, ()> {
+        Box::pin(self.play_clips(items))
+    }
+
+    fn stop(&self) -> BackendFuture<'_, ()> {
+        Box::pin(self.stop_media())
+    }
+}
+
+impl OverlayBackend for Obs {
+    fn set_input_settings<'a>(
+        &'a self,
+        input: &'a str,
+        settings: &'a Value,
+    ) -> BackendFuture<'a, ()> {
+        Box::pin(Obs::set_input_settings(self, input, settings))
+    }
+}
+
+// 名前付きの録画先。1回のトリガーですべての録画先に保存する
+#[derive(Clone)]
+pub struct CaptureTarget {
+    pub name: String,
+    pub backend: Arc<dyn ClipBackend>,
+}
+
+// 録画・再生・表示をまとめて持つ。OBSが1台なら同じ接続をすべてに使う
+#[derive(Clone)]
+pub struct Backends {
+    pub capture: Vec<CaptureTarget>,
+    pub playback: Arc<dyn PlaybackBackend>,
+    pub overlay: Arc<dyn OverlayBackend>,
+}
+
+// テスト用のメモリ上の実装
+// 保存するたびに連番のパスを作り、再生したプレイリストを記録する
+#[cfg(test)]
+pub mod fake {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    #[derive(Default)]
+    pub struct FakeBackend {
+        pub fail_save: bool,
+        // 書き出しに時間のかかる録画先の代わりに、保存を遅らせる
+        pub save_delay: std::time::Duration,
+        pub saved: Mutex<Vec<PathBuf>>,
+        // 停止は空のプレイリストとして記録する
+        pub played: Mutex<Vec<Vec<PathBuf>>>,
+        // (input, settings)
+        pub inputs: Mutex<Vec<(String, Value)>>,
+        pub listener: Mutex<Option<Sender<PathBuf>>>,
+    }
+
+    impl FakeBackend {
+        pub fn saved_clips(&self) -> Vec<PathBuf> {
+            self.saved.lock().unwrap().clone()
+        }
+    }
+
+    impl ClipBackend for FakeBackend {
+        fn save_replay(&self) -> BackendFuture<'_, ()> {
+            Box::pin(async move {
+                if self.fail_save {
+                    return Err("replay buffer is not active".to_string());
+                }
+                tokio::time::sleep(self.save_delay).await;
+                let path = {
+                    let mut saved = self.saved.lock().unwrap();
+                    let path = PathBuf::from(format!("fake/Replay {}.mkv", saved.len() + 1));
+                    saved.push(path.clone());
+                    path
+                };
+                let tx = self.listener.lock().unwrap().clone();
+                if let Some(tx) = tx {
+                    tx.send(path).await.map_err(|e| e.to_string())?;
+                }
+                Ok(())
+            })
+        }
+
+        fn listen_saved_clips(&self, tx: Sender<PathBuf>) -> BackendFuture<'_, ()> {
+            *self.listener.lock().unwrap() = Some(tx);
+            Box::pin(async { Ok(()) })
+        }
+
+        fn list_saved_clips(&self) -> BackendFuture<'_, Vec<PathBuf>> {
+            Box::pin(async { Ok(self.saved_clips()) })
+        }
+    }
+
+    impl PlaybackBackend for FakeBackend {
+        fn play_playlist<'a>(&'a self, items: &'a [PlaybackItem]) -> BackendFuture<'a, ()> {
+            let paths = items.iter().map(|item| item.path.clone()).collect();
+            self.played.lock().unwrap().push(paths);
+            Box::pin(async { Ok(()) })
+        }
+
+        fn stop(&self) -> BackendFuture<'_, ()> {
+            self.played.lock().unwrap().push(Vec::new());
+            Box::pin(async { Ok(()) })
+        }
+    }
+
+    impl OverlayBackend for FakeBackend {
+        fn set_input_settings<'a>(
+            &'a self,
+            input: &'a str,
+            settings: &'a Value,
+        ) -> BackendFuture<'a, ()> {
+            self.inputs
+                .lock()
+                .unwrap()
+                .push((input.to_string(), settings.clone()));
+            Box::pin(async { Ok(()) })
+        }
+    }
+}
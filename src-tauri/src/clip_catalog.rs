@@ -0,0 +1,536 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::clip_storage::RelinkReport;
+use crate::match_state::{MatchState, Side};
+use crate::media_probe::MediaInfo;
+
+// 1台のOBSのリプレイバッファだけで録画するときのアングル名
+pub const DEFAULT_ANGLE: &str = "main";
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ClipKind {
+    Goal,
+    EpicSave,
+    // Mugiのトリガーに紐付かない保存(OBS側で手動保存など)
+    Manual,
+}
+
+// クリップ保存時点の試合状況
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ClipContext {
+    pub kind: ClipKind,
+    pub match_id: Option<String>,
+    pub game_number: u32,
+    pub clock: Option<u32>,
+    pub is_overtime: bool,
+    pub scorer: Option<String>,
+    pub team: Option<Side>,
+    pub team_name: Option<String>,
+    pub blue_goals: u32,
+    pub orange_goals: u32,
+    // このプレーでのボールタッチ数とデモ数
+    pub touches: u32,
+    pub demos: u32,
+    // 外部ツールからのクリップ要求に付いていたメモ
+    pub label: Option<String>,
+    // 録画したOBS、またはSource Recordのアングルの名前
+    pub angle: String,
+    // 同じトリガーで各アングルに保存されたクリップに共通の番号
+    pub highlight_id: Option<u64>,
+}
+
+impl ClipContext {
+    pub fn from_state(kind: ClipKind, state: &MatchState) -> Self {
+        let (scorer, team, touches, demos) = match (kind, &state.last_goal) {
+            (ClipKind::Goal, Some(goal)) => (
+                Some(goal.score_id.clone()),
+                state.side_of(&goal.team),
+                state.last_goal_touches,
+                state.last_goal_demos,
+            ),
+            _ => (None, None, state.play_touches, state.play_demos),
+        };
+        let team_name = team
+            .map(|side| state.team_name(side).to_string())
+            .filter(|name| !name.is_empty());
+        Self {
+            kind,
+            match_id: state.match_id.clone(),
+            game_number: state.game_number,
+            clock: state.clock,
+            is_overtime: state.is_overtime,
+            scorer,
+            team,
+            team_name,
+            blue_goals: state.blue_goals,
+            orange_goals: state.orange_goals,
+            touches,
+            demos,
+            label: None,
+            angle: DEFAULT_ANGLE.to_string(),
+            highlight_id: None,
+        }
+    }
+
+    // トリガー無しに保存されたクリップ(OBSでの手動保存など)
+    fn manual(angle: &str) -> Self {
+        Self {
+            kind: ClipKind::Manual,
+            match_id: None,
+            game_number: 0,
+            clock: None,
+            is_overtime: false,
+            scorer: None,
+            team: None,
+            team_name: None,
+            blue_goals: 0,
+            orange_goals: 0,
+            touches: 0,
+            demos: 0,
+            label: None,
+            angle: angle.to_string(),
+            highlight_id: None,
+        }
+    }
+}
+
+// 再エンコードせずに再生範囲を絞るためのin/out点(クリップ先頭からの秒数)
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Trim {
+    pub in_sec: Option<f64>,
+    pub out_sec: Option<f64>,
+}
+
+impl Trim {
+    fn validate(&self) -> Result<(), String> {
+        if self.in_sec.is_some_and(|t| t < 0.0) || self.out_sec.is_some_and(|t| t <= 0.0) {
+            return Err("in/out点は正の秒数で指定してください".to_string());
+        }
+        if let (Some(in_sec), Some(out_sec)) = (self.in_sec, self.out_sec)
+            && in_sec >= out_sec
+        {
+            return Err("out点はin点より後にしてください".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Clip {
+    pub id: u64,
+    pub path: PathBuf,
+    // UNIX時間(秒)
+    pub saved_at: u64,
+    pub duration_sec: Option<f64>,
+    pub media: Option<MediaInfo>,
+    pub thumbnail: Option<PathBuf>,
+    pub trim: Trim,
+    pub favorite: bool,
+    pub play_count: u32,
+    pub context: ClipContext,
+}
+
+impl Clip {
+    // トリム後の再生時間
+    pub fn playback_duration_sec(&self) -> Option<f64> {
+        let out_sec = self.trim.out_sec.or(self.duration_sec)?;
+        Some((out_sec - self.trim.in_sec.unwrap_or(0.0)).max(0.0))
+    }
+}
+
+// ハイライトの中の1つのアングルのクリップ
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ClipAngle {
+    pub angle: String,
+    pub clip_id: u64,
+    pub path: PathBuf,
+    pub trim: Trim,
+}
+
+// 1回のトリガーで各アングルに保存されたクリップのまとまり
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Highlight {
+    pub id: u64,
+    // 最初に保存されたアングルの試合状況
+    pub context: ClipContext,
+    // 保存順
+    pub angles: Vec<ClipAngle>,
+}
+
+impl Highlight {
+    // orderの順にアングルを並べる。orderが空なら保存順にすべてのアングル
+    // 録画されていないアングルは飛ばす
+    pub fn playlist(&self, order: &[String]) -> Vec<&ClipAngle> {
+        if order.is_empty() {
+            return self.angles.iter().collect();
+        }
+        order
+            .iter()
+            .filter_map(|name| self.angles.iter().find(|a| a.angle == *name))
+            .collect()
+    }
+}
+
+// ハイライトを再生するときのアングルの順番(番組用カメラ→ボールカメラなど)
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct AnglePlaybackSettings {
+    // 空なら保存された全アングルを保存順に再生する
+    pub order: Vec<String>,
+}
+
+// 保存されたリプレイと、その時の試合状況の一覧
+#[derive(Debug, Default)]
+pub struct ClipCatalog {
+    clips: Vec<Clip>,
+    // save_replay_buffer済みでReplayBufferSavedを待っているトリガー
+    pending: VecDeque<ClipContext>,
+    // 終了した試合の最終スコア (blue, orange)
+    results: HashMap<String, (u32, u32)>,
+    next_id: u64,
+    next_highlight_id: u64,
+    // ハイライトごとに最初に保存を頼んだアングル(録画先の先頭にあるリプレイバッファ)
+    primary_angles: HashMap<u64, String>,
+}
+
+impl ClipCatalog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push_pending(&mut self, context: ClipContext) {
+        if let Some(id) = context.highlight_id {
+            self.primary_angles
+                .entry(id)
+                .or_insert_with(|| context.angle.clone());
+        }
+        self.pending.push_back(context);
+    }
+
+    // 保存に失敗したトリガーを取り消す
+    pub fn cancel_pending(&mut self, context: &ClipContext) {
+        if let Some(index) = self
+            .pending
+            .iter()
+            .rposition(|c| c.angle == context.angle && c.highlight_id == context.highlight_id)
+        {
+            self.pending.remove(index);
+        }
+    }
+
+    pub fn has_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    // 1回のトリガーで保存する全アングルのクリップをまとめる番号を払い出す
+    pub fn new_highlight_id(&mut self) -> u64 {
+        let id = self.next_highlight_id;
+        self.next_highlight_id += 1;
+        id
+    }
+
+    // angleで保存されたpathを、そのアングルを待っている古いトリガーから順に紐付ける
+    // 待っているトリガーが無ければ、他のアングルのトリガーは使わずに手動保存として登録する
+    pub fn attach_angle(&mut self, angle: &str, path: PathBuf) -> Clip {
+        let context = match self.pending.iter().position(|c| c.angle == angle) {
+            Some(index) => self.pending.remove(index).unwrap(),
+            None => ClipContext::manual(angle),
+        };
+        self.insert(path, context)
+    }
+
+    // pathをアングルに関係なく古いトリガーから順に紐付ける
+    // 保存されたクリップはどれもアングル付きで届くので、テストで既定のアングルの保存を作るときだけ使う
+    #[cfg(test)]
+    pub fn attach(&mut self, path: PathBuf) -> Clip {
+        let context = self
+            .pending
+            .pop_front()
+            .unwrap_or_else(|| ClipContext::manual(DEFAULT_ANGLE));
+        self.insert(path, context)
+    }
+
+    fn insert(&mut self, path: PathBuf, context: ClipContext) -> Clip {
+        let saved_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        let clip = Clip {
+            id: self.next_id,
+            path,
+            saved_at,
+            duration_sec: None,
+            media: None,
+            thumbnail: None,
+            trim: Trim::default(),
+            favorite: false,
+            play_count: 0,
+            context,
+        };
+        self.next_id += 1;
+        self.clips.push(clip.clone());
+        clip
+    }
+
+    pub fn clips(&self) -> &[Clip] {
+        &self.clips
+    }
+
+    // 新しく保存されたn個のプレーを保存順に返す。ハイライトは代表するアングルのクリップ1つ
+    pub fn latest(&self, n: usize) -> Vec<&Clip> {
+        let clips = self.highlight_clips();
+        clips[clips.len().saturating_sub(n)..].to_vec()
+    }
+
+    pub fn set_trim(&mut self, id: u64, trim: Trim) -> Result<Clip, String> {
+        trim.validate()?;
+        let clip = self
+            .clips
+            .iter_mut()
+            .find(|c| c.id == id)
+            .ok_or_else(|| format!("クリップ{}が見つかりません", id))?;
+        clip.trim = trim;
+        Ok(clip.clone())
+    }
+
+    // ffprobeの結果とサムネイルを記録する
+    pub fn set_media(
+        &mut self,
+        id: u64,
+        media: MediaInfo,
+        thumbnail: Option<PathBuf>,
+    ) -> Option<Clip> {
+        let clip = self.clips.iter_mut().find(|c| c.id == id)?;
+        clip.duration_sec = media.duration_sec;
+        clip.media = Some(media);
+        clip.thumbnail = thumbnail;
+        Some(clip.clone())
+    }
+
+    pub fn set_path(&mut self, id: u64, path: PathBuf) -> Option<Clip> {
+        let clip = self.clips.iter_mut().find(|c| c.id == id)?;
+        clip.path = path;
+        Some(clip.clone())
+    }
+
+    // 保存フォルダを移動したとき、old_root配下のクリップをnew_root配下の同じ相対パスに付け替える
+    pub fn relink(&mut self, old_root: &Path, new_root: &Path) -> RelinkReport {
+        let mut report = RelinkReport::default();
+        for clip in self.clips.iter_mut() {
+            let Ok(relative) = clip.path.strip_prefix(old_root) else {
+                continue;
+            };
+            let path = new_root.join(relative);
+            if path.exists() {
+                clip.path = path;
+                report.relinked += 1;
+            } else {
+                report.missing.push(path);
+            }
+        }
+        report
+    }
+
+    pub fn get(&self, id: u64) -> Option<&Clip> {
+        self.clips.iter().find(|c| c.id == id)
+    }
+
+    pub fn set_favorite(&mut self, id: u64, favorite: bool) -> Result<Clip, String> {
+        let clip = self
+            .clips
+            .iter_mut()
+            .find(|c| c.id == id)
+            .ok_or_else(|| format!("クリップ{}が見つかりません", id))?;
+        clip.favorite = favorite;
+        Ok(clip.clone())
+    }
+
+    pub fn mark_played(&mut self, paths: &[PathBuf]) {
+        for clip in self.clips.iter_mut().filter(|c| paths.contains(&c.path)) {
+            clip.play_count += 1;
+        }
+    }
+
+    pub fn remove(&mut self, id: u64) -> Option<Clip> {
+        let index = self.clips.iter().position(|c| c.id == id)?;
+        Some(self.clips.remove(index))
+    }
+
+    // 同じトリガーで保存されたクリップをハイライトにまとめる。古い順
+    pub fn highlights(&self) -> Vec<Highlight> {
+        let mut highlights: Vec<Highlight> = Vec::new();
+        for clip in &self.clips {
+            let Some(id) = clip.context.highlight_id else {
+                continue;
+            };
+            let angle = ClipAngle {
+                angle: clip.context.angle.clone(),
+                clip_id: clip.id,
+                path: clip.path.clone(),
+                trim: clip.trim,
+            };
+            match highlights.iter_mut().find(|h| h.id == id) {
+                Some(highlight) => highlight.angles.push(angle),
+                None => highlights.push(Highlight {
+                    id,
+                    context: clip.context.clone(),
+                    angles: vec![angle],
+                }),
+            }
+        }
+        highlights.sort_by_key(|h| h.id);
+        highlights
+    }
+
+    pub fn highlight(&self, id: u64) -> Option<Highlight> {
+        self.highlights().into_iter().find(|h| h.id == id)
+    }
+
+    pub fn find_by_path(&self, path: &Path) -> Option<&Clip> {
+        self.clips.iter().find(|c| c.path == path)
+    }
+
+    pub fn record_result(&mut self, match_id: String, blue_goals: u32, orange_goals: u32) {
+        self.results.insert(match_id, (blue_goals, orange_goals));
+    }
+
+    pub fn result(&self, match_id: &str) -> Option<(u32, u32)> {
+        self.results.get(match_id).copied()
+    }
+
+    // 1つのプレーを1つのクリップで返す。ハイライトは最初に保存を頼んだアングルのクリップで代表し
+    // (Source Recordのアングルは書き終わるまで届かないので、届いた順では決めない)、
+    // そのアングルが無ければ最初に届いたアングル。ハイライトに属さないクリップはそのまま
+    pub fn highlight_clips(&self) -> Vec<&Clip> {
+        let representatives: HashSet<u64> = self
+            .highlights()
+            .iter()
+            .map(|h| {
+                let primary = self.primary_angles.get(&h.id);
+                h.angles
+                    .iter()
+                    .find(|a| Some(&a.angle) == primary)
+                    .unwrap_or(&h.angles[0])
+                    .clip_id
+            })
+            .collect();
+        self.clips
+            .iter()
+            .filter(|c| c.context.highlight_id.is_none() || representatives.contains(&c.id))
+            .collect()
+    }
+
+    pub fn clips_for_match(&self, match_id: &str) -> Vec<&Clip> {
+        self.highlight_clips()
+            .into_iter()
+            .filter(|c| c.context.match_id.as_deref() == Some(match_id))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn pending(catalog: &mut ClipCatalog, angle: &str, highlight_id: u64) {
+        catalog.push_pending(ClipContext {
+            highlight_id: Some(highlight_id),
+            ..ClipContext::manual(angle)
+        });
+    }
+
+    #[test]
+    fn test_highlights() {
+        let mut catalog = ClipCatalog::new();
+        for id in 0..2 {
+            pending(&mut catalog, "program", id);
+            pending(&mut catalog, "ball_cam", id);
+        }
+        catalog.attach_angle("program", PathBuf::from("program_0.mkv"));
+        catalog.attach_angle("ball_cam", PathBuf::from("ball_cam_0.mkv"));
+        catalog.attach_angle("program", PathBuf::from("program_1.mkv"));
+        catalog.attach_angle("ball_cam", PathBuf::from("ball_cam_1.mkv"));
+        // トリガーに紐付かない保存はハイライトにしない
+        catalog.attach(PathBuf::from("manual.mkv"));
+
+        let highlights = catalog.highlights();
+        assert_eq!(highlights.len(), 2);
+        let highlight = &highlights[1];
+        assert_eq!(highlight.id, 1);
+        let angles: Vec<&str> = highlight.angles.iter().map(|a| a.angle.as_str()).collect();
+        assert_eq!(angles, ["program", "ball_cam"]);
+
+        // 指定した順番で、録画されていないアングルは飛ばす
+        let order = ["ball_cam", "iso", "program"].map(String::from);
+        let paths: Vec<&Path> = highlight
+            .playlist(&order)
+            .iter()
+            .map(|a| a.path.as_path())
+            .collect();
+        assert_eq!(
+            paths,
+            [Path::new("ball_cam_1.mkv"), Path::new("program_1.mkv")]
+        );
+        assert_eq!(highlight.playlist(&[]).len(), 2);
+    }
+
+    #[test]
+    fn test_highlight_clips_prefer_primary_angle() {
+        let mut catalog = ClipCatalog::new();
+        for id in 0..2 {
+            pending(&mut catalog, "program", id);
+            pending(&mut catalog, "ball_cam", id);
+        }
+        // ボールカメラが先に届いても、ハイライトは番組用のアングルで代表する
+        catalog.attach_angle("ball_cam", PathBuf::from("ball_cam_0.mkv"));
+        catalog.attach_angle("program", PathBuf::from("program_0.mkv"));
+        catalog.attach_angle("program", PathBuf::from("program_1.mkv"));
+        catalog.attach_angle("ball_cam", PathBuf::from("ball_cam_1.mkv"));
+        catalog.attach(PathBuf::from("manual.mkv"));
+
+        let paths: Vec<&Path> = catalog
+            .highlight_clips()
+            .iter()
+            .map(|c| c.path.as_path())
+            .collect();
+        assert_eq!(
+            paths,
+            ["program_0.mkv", "program_1.mkv", "manual.mkv"].map(Path::new)
+        );
+        // 最新のn個もアングルごとではなくプレーごとに数える
+        let paths: Vec<&Path> = catalog.latest(2).iter().map(|c| c.path.as_path()).collect();
+        assert_eq!(paths, ["program_1.mkv", "manual.mkv"].map(Path::new));
+    }
+
+    #[test]
+    fn test_attach_angle_without_pending() {
+        let mut catalog = ClipCatalog::new();
+        pending(&mut catalog, "program", 0);
+
+        // 待っていないアングルのクリップは、他のアングルのトリガーを取らずに手動保存になる
+        let clip = catalog.attach_angle("ball_cam", PathBuf::from("ball_cam.mkv"));
+        assert_eq!(clip.context.kind, ClipKind::Manual);
+        assert_eq!(clip.context.angle, "ball_cam");
+        assert_eq!(clip.context.highlight_id, None);
+        assert!(catalog.has_pending());
+
+        let clip = catalog.attach_angle("program", PathBuf::from("program.mkv"));
+        assert_eq!(clip.context.angle, "program");
+        assert_eq!(clip.context.highlight_id, Some(0));
+        assert!(!catalog.has_pending());
+
+        // アングルの分からない保存は既定のアングルになる
+        let clip = catalog.attach(PathBuf::from("manual.mkv"));
+        assert_eq!(clip.context.angle, DEFAULT_ANGLE);
+    }
+}
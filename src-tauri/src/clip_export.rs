@@ -0,0 +1,344 @@
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use tauri::Emitter;
+
+use crate::clip_catalog::ClipCatalog;
+use crate::media_probe;
+
+// タイトルカードの表示時間(秒)
+const TITLE_CARD_SEC: f64 = 3.0;
+
+// 書き出しとサムネイル生成で使う外部ツールの設定
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportSettings {
+    // PATHが通っていればffmpegのままで良い
+    pub ffmpeg_path: PathBuf,
+    // drawtextで使うフォント。Noneならffmpegの既定(fontconfig)に任せる
+    pub font_file: Option<PathBuf>,
+    pub width: u32,
+    pub height: u32,
+    pub fps: u32,
+}
+
+impl ExportSettings {
+    // ffprobeはffmpegと同じ場所にある前提
+    pub fn ffprobe_path(&self) -> PathBuf {
+        let mut path = self.ffmpeg_path.clone();
+        let ext = path.extension().map(|e| e.to_os_string());
+        path.set_file_name("ffprobe");
+        if let Some(ext) = ext {
+            path.set_extension(ext);
+        }
+        path
+    }
+}
+
+impl Default for ExportSettings {
+    fn default() -> Self {
+        Self {
+            ffmpeg_path: PathBuf::from("ffmpeg"),
+            font_file: None,
+            width: 1920,
+            height: 1080,
+            fps: 60,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportRequest {
+    pub clip_ids: Vec<u64>,
+    pub output: PathBuf,
+    pub title: Option<String>,
+    // 得点者とチーム名をテロップとして重ねる
+    pub lower_third: bool,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportProgress {
+    pub step: usize,
+    pub total: usize,
+    pub message: String,
+}
+
+#[derive(Debug, Clone)]
+enum Segment {
+    Title(String),
+    Clip {
+        path: PathBuf,
+        in_sec: Option<f64>,
+        out_sec: Option<f64>,
+        lower_third: Option<String>,
+    },
+}
+
+// 書き出しに必要な情報をカタログから取り出したもの
+#[derive(Debug, Clone)]
+pub struct ExportJob {
+    segments: Vec<Segment>,
+    output: PathBuf,
+}
+
+pub fn build_job(catalog: &ClipCatalog, request: &ExportRequest) -> Result<ExportJob, String> {
+    if request.clip_ids.is_empty() {
+        return Err("書き出すクリップがありません".to_string());
+    }
+    let mut segments = Vec::new();
+    if let Some(title) = request.title.as_ref().filter(|t| !t.is_empty()) {
+        segments.push(Segment::Title(title.clone()));
+    }
+    for id in &request.clip_ids {
+        let clip = catalog
+            .get(*id)
+            .ok_or_else(|| format!("クリップ{}が見つかりません", id))?;
+        let lower_third = if request.lower_third {
+            let ctx = &clip.context;
+            let text = [ctx.scorer.as_deref(), ctx.team_name.as_deref()]
+                .into_iter()
+                .flatten()
+                .collect::<Vec<_>>()
+                .join(" - ");
+            Some(text).filter(|t| !t.is_empty())
+        } else {
+            None
+        };
+        segments.push(Segment::Clip {
+            path: clip.path.clone(),
+            in_sec: clip.trim.in_sec,
+            out_sec: clip.trim.out_sec,
+            lower_third,
+        });
+    }
+    Ok(ExportJob {
+        segments,
+        output: request.output.clone(),
+    })
+}
+
+// drawtextのtext/fontfileに渡す値のエスケープ
+fn escape_drawtext(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        if matches!(c, '\\' | ':' | '\'' | '%' | ',' | ';' | '[' | ']') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn drawtext(settings: &ExportSettings, text: &str, position: &str, size: u32) -> String {
+    let mut filter = format!(
+        "drawtext=text={}:fontcolor=white:fontsize={}:{}",
+        escape_drawtext(text),
+        size,
+        position
+    );
+    if let Some(font) = &settings.font_file {
+        // Windowsのパス区切りはffmpegで扱いにくいので/に揃える
+        let font = font.to_string_lossy().replace('\\', "/");
+        filter.push_str(&format!(":fontfile={}", escape_drawtext(&font)));
+    }
+    filter
+}
+
+// 1本分を共通の解像度・コーデックに揃えてエンコードする引数
+fn segment_args(settings: &ExportSettings, segment: &Segment, output: &Path) -> Vec<String> {
+    let (w, h) = (settings.width, settings.height);
+    let mut args: Vec<String> = vec!["-y".into(), "-hide_banner".into()];
+    let mut filters = Vec::new();
+    match segment {
+        Segment::Title(title) => {
+            args.extend([
+                "-f".into(),
+                "lavfi".into(),
+                "-i".into(),
+                format!("color=c=black:s={}x{}:r={}", w, h, settings.fps),
+                "-f".into(),
+                "lavfi".into(),
+                "-i".into(),
+                "anullsrc=r=48000:cl=stereo".into(),
+                "-t".into(),
+                TITLE_CARD_SEC.to_string(),
+            ]);
+            filters.push(drawtext(
+                settings,
+                title,
+                "x=(w-text_w)/2:y=(h-text_h)/2",
+                h / 10,
+            ));
+        }
+        Segment::Clip {
+            path,
+            in_sec,
+            out_sec,
+            lower_third,
+        } => {
+            if let Some(in_sec) = in_sec {
+                args.extend(["-ss".into(), in_sec.to_string()]);
+            }
+            if let Some(out_sec) = out_sec {
+                args.extend(["-to".into(), out_sec.to_string()]);
+            }
+            args.extend(["-i".into(), path.to_string_lossy().into_owned()]);
+            filters.push(format!(
+                "scale={w}:{h}:force_original_aspect_ratio=decrease,pad={w}:{h}:(ow-iw)/2:(oh-ih)/2,setsar=1,fps={}",
+                settings.fps
+            ));
+            if let Some(text) = lower_third {
+                filters.push(drawtext(
+                    settings,
+                    text,
+                    "x=h/20:y=h-text_h-h/20:box=1:boxcolor=black@0.6:boxborderw=12",
+                    h / 20,
+                ));
+            }
+        }
+    }
+    args.extend([
+        "-vf".into(),
+        filters.join(","),
+        "-c:v".into(),
+        "libx264".into(),
+        "-preset".into(),
+        "veryfast".into(),
+        "-crf".into(),
+        "20".into(),
+        "-pix_fmt".into(),
+        "yuv420p".into(),
+        "-c:a".into(),
+        "aac".into(),
+        "-ar".into(),
+        "48000".into(),
+        "-ac".into(),
+        "2".into(),
+        output.to_string_lossy().into_owned(),
+    ]);
+    args
+}
+
+async fn run_ffmpeg(settings: &ExportSettings, args: &[String]) -> Result<(), String> {
+    media_probe::run_tool(&settings.ffmpeg_path, args).await?;
+    Ok(())
+}
+
+fn emit_progress(app_handle: &tauri::AppHandle, step: usize, total: usize, message: String) {
+    let progress = ExportProgress {
+        step,
+        total,
+        message,
+    };
+    if let Err(e) = app_handle.emit("export_progress", progress) {
+        error!("Failed to emit export_progress event: {}", e);
+    }
+}
+
+async fn export(
+    settings: &ExportSettings,
+    job: &ExportJob,
+    work_dir: &Path,
+    app_handle: &tauri::AppHandle,
+) -> Result<(), String> {
+    std::fs::create_dir_all(work_dir).map_err(|e| format!("Failed to create work dir: {e}"))?;
+    // 各セグメント + 結合
+    let total = job.segments.len() + 1;
+    let mut list = String::new();
+    for (i, segment) in job.segments.iter().enumerate() {
+        emit_progress(
+            app_handle,
+            i,
+            total,
+            format!("{}/{}をエンコード中", i + 1, total - 1),
+        );
+        let part = work_dir.join(format!("part_{:03}.mp4", i));
+        run_ffmpeg(settings, &segment_args(settings, segment, &part)).await?;
+        let part = part
+            .to_string_lossy()
+            .replace('\\', "/")
+            .replace('\'', "'\\''");
+        list.push_str(&format!("file '{}'\n", part));
+    }
+
+    emit_progress(app_handle, total - 1, total, "結合中".to_string());
+    let list_path = work_dir.join("concat.txt");
+    std::fs::write(&list_path, list).map_err(|e| format!("Failed to write concat list: {e}"))?;
+    let args: Vec<String> = vec![
+        "-y".into(),
+        "-hide_banner".into(),
+        "-f".into(),
+        "concat".into(),
+        "-safe".into(),
+        "0".into(),
+        "-i".into(),
+        list_path.to_string_lossy().into_owned(),
+        "-c".into(),
+        "copy".into(),
+        job.output.to_string_lossy().into_owned(),
+    ];
+    run_ffmpeg(settings, &args).await
+}
+
+// 別タスクで書き出し、進捗と結果をイベントで通知する
+pub fn spawn_export(settings: ExportSettings, job: ExportJob, app_handle: tauri::AppHandle) {
+    tokio::spawn(async move {
+        let stamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or_default();
+        let work_dir = std::env::temp_dir().join(format!("rl_replay_export_{}", stamp));
+        let res = export(&settings, &job, &work_dir, &app_handle).await;
+        if let Err(e) = std::fs::remove_dir_all(&work_dir) {
+            error!("Failed to remove {:?}: {}", work_dir, e);
+        }
+        match res {
+            Ok(()) => {
+                info!("Exported highlights to {:?}", job.output);
+                let total = job.segments.len() + 1;
+                emit_progress(&app_handle, total, total, "完了".to_string());
+                if let Err(e) = app_handle.emit("export_finished", &job.output) {
+                    error!("Failed to emit export_finished event: {}", e);
+                }
+            }
+            Err(e) => {
+                error!("Failed to export highlights: {}", e);
+                if let Err(e) = app_handle.emit("export_failed", e) {
+                    error!("Failed to emit export_failed event: {}", e);
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_escape_drawtext() {
+        assert_eq!(escape_drawtext("Blue: 1'0"), "Blue\\: 1\\'0");
+    }
+
+    #[test]
+    fn test_segment_args() {
+        let settings = ExportSettings::default();
+        let segment = Segment::Clip {
+            path: PathBuf::from("goal.mkv"),
+            in_sec: Some(1.5),
+            out_sec: None,
+            lower_third: Some("scorer - Team".to_string()),
+        };
+        let args = segment_args(&settings, &segment, Path::new("part.mp4"));
+        let ss = args.iter().position(|a| a == "-ss").unwrap();
+        assert_eq!(args[ss + 1], "1.5");
+        assert!(!args.contains(&"-to".to_string()));
+        let vf = args.iter().position(|a| a == "-vf").unwrap();
+        assert!(args[vf + 1].contains("drawtext=text=scorer - Team"));
+        assert_eq!(args.last().unwrap(), "part.mp4");
+    }
+}
@@ -0,0 +1,148 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::clip_catalog::{Clip, ClipKind};
+use crate::match_state::Side;
+
+// 試合ごとのフォルダは保存先の設定(clip_storage)で作るので、既定はファイル名だけにする
+const DEFAULT_TEMPLATE: &str = "{game}_{clock}_{team}_{scorer}_{kind}.{ext}";
+
+// 保存されたリプレイのリネーム設定
+// 使えるプレースホルダ: {match_id} {game} {clock} {team} {scorer} {kind} {angle} {id} {ext}
+// テンプレート内の/はフォルダ区切りとして扱う
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RenameSettings {
+    pub enabled: bool,
+    pub template: String,
+}
+
+impl Default for RenameSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            template: DEFAULT_TEMPLATE.to_string(),
+        }
+    }
+}
+
+// Windowsでファイル名に使えない文字を置き換える
+pub fn sanitize(value: &str) -> String {
+    let value: String = value
+        .chars()
+        .map(|c| match c {
+            '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    let value = value.trim().trim_end_matches('.');
+    if value.is_empty() {
+        "none".to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+fn placeholder(clip: &Clip, name: &str) -> Option<String> {
+    let ctx = &clip.context;
+    let value = match name {
+        "match_id" => ctx.match_id.clone().unwrap_or_default(),
+        "game" => format!("G{}", ctx.game_number),
+        "clock" => match ctx.clock {
+            Some(clock) if ctx.is_overtime => format!("OT+{}-{:02}", clock / 60, clock % 60),
+            Some(clock) => format!("{}-{:02}", clock / 60, clock % 60),
+            None => String::new(),
+        },
+        "team" => ctx.team_name.clone().unwrap_or_else(|| match ctx.team {
+            Some(Side::Blue) => "blue".to_string(),
+            Some(Side::Orange) => "orange".to_string(),
+            None => String::new(),
+        }),
+        "scorer" => ctx.scorer.clone().unwrap_or_default(),
+        "kind" => match ctx.kind {
+            ClipKind::Goal => "goal",
+            ClipKind::EpicSave => "save",
+            ClipKind::Manual => "manual",
+        }
+        .to_string(),
+        "angle" => ctx.angle.clone(),
+        "id" => clip.id.to_string(),
+        "ext" => clip
+            .path
+            .extension()
+            .map(|e| e.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "mkv".to_string()),
+        _ => return None,
+    };
+    Some(sanitize(&value))
+}
+
+// テンプレートを展開した相対パスを返す
+pub fn render_template(template: &str, clip: &Clip) -> PathBuf {
+    let mut path = PathBuf::new();
+    for part in template.split(['/', '\\']).filter(|p| !p.is_empty()) {
+        let mut rendered = String::new();
+        let mut rest = part;
+        while let Some(start) = rest.find('{') {
+            rendered.push_str(&rest[..start]);
+            let after = &rest[start + 1..];
+            match after.find('}') {
+                Some(end) => {
+                    let name = &after[..end];
+                    match placeholder(clip, name) {
+                        Some(value) => rendered.push_str(&value),
+                        // 未知のプレースホルダはそのまま残す
+                        None => rendered.push_str(&rest[start..start + end + 2]),
+                    }
+                    rest = &after[end + 1..];
+                }
+                None => {
+                    rendered.push_str(&rest[start..]);
+                    rest = "";
+                }
+            }
+        }
+        rendered.push_str(rest);
+        path.push(sanitize(&rendered));
+    }
+    path
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::clip_catalog::{ClipCatalog, ClipContext};
+    use crate::match_state::MatchState;
+    use crate::mugi_schema::Goals;
+    use std::path::Path;
+
+    #[test]
+    fn test_render_template() {
+        let state = MatchState {
+            match_id: Some("ABC".to_string()),
+            game_number: 2,
+            blue_name: "Team:A".to_string(),
+            clock: Some(65),
+            last_goal: Some(Goals {
+                team: "blue".to_string(),
+                score_id: "Player_1".to_string(),
+                assist_id: String::new(),
+            }),
+            ..MatchState::default()
+        };
+        let mut catalog = ClipCatalog::new();
+        catalog.push_pending(ClipContext::from_state(ClipKind::Goal, &state));
+        let clip = catalog.attach(PathBuf::from("Replay 2025-01-01.mp4"));
+
+        let path = render_template(DEFAULT_TEMPLATE, &clip);
+        assert_eq!(path, Path::new("G2_1-05_Team_A_Player_1_goal.mp4"));
+        let path = render_template("{match_id}/{game}.{ext}", &clip);
+        assert_eq!(path, Path::new("ABC").join("G2.mp4"));
+        let path = render_template("{kind}_{unknown}", &clip);
+        assert_eq!(path, Path::new("goal_{unknown}"));
+        let path = render_template("{angle}_{id}.{ext}", &clip);
+        assert_eq!(path, Path::new("main_0.mp4"));
+    }
+}
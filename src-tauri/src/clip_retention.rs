@@ -0,0 +1,227 @@
+use std::collections::HashSet;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use crate::clip_catalog::{Clip, ClipCatalog, ClipKind};
+use crate::clip_storage::StorageSettings;
+
+// 空き容量の監視と古いクリップの削除ポリシー
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RetentionSettings {
+    // 空き容量がこれを下回ったら警告する(MB)
+    pub min_free_mb: u64,
+    // 直近何試合分の未再生クリップを残すか
+    pub keep_games: u32,
+    // 空き容量不足のときに自動でポリシーを適用する
+    pub auto_apply: bool,
+    pub check_interval_sec: u64,
+}
+
+impl Default for RetentionSettings {
+    fn default() -> Self {
+        Self {
+            min_free_mb: 10 * 1024,
+            keep_games: 6,
+            auto_apply: false,
+            check_interval_sec: 60,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DiskStatus {
+    pub dir: PathBuf,
+    pub free_bytes: u64,
+    pub min_free_bytes: u64,
+    pub is_low: bool,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RetentionReport {
+    pub dry_run: bool,
+    // 削除した(dry_runなら削除対象の)クリップ
+    pub clips: Vec<Clip>,
+    pub freed_bytes: u64,
+    pub errors: Vec<String>,
+}
+
+#[cfg(windows)]
+fn free_space(path: &Path) -> io::Result<u64> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Storage::FileSystem::GetDiskFreeSpaceExW;
+
+    let wide: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+    let mut free: u64 = 0;
+    let ok = unsafe {
+        GetDiskFreeSpaceExW(
+            wide.as_ptr(),
+            &mut free,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+        )
+    };
+    if ok == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(free)
+}
+
+#[cfg(unix)]
+fn free_space(path: &Path) -> io::Result<u64> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = std::ffi::CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    #[allow(clippy::unnecessary_cast)]
+    Ok(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+// 監視するフォルダ: 整理先のrootか、なければ最後に保存されたクリップのフォルダ
+pub fn watch_dir(catalog: &ClipCatalog, storage: &StorageSettings) -> Option<PathBuf> {
+    if let Some(root) = &storage.root {
+        return Some(root.clone());
+    }
+    catalog
+        .clips()
+        .last()
+        .and_then(|c| c.path.parent())
+        .map(Path::to_path_buf)
+}
+
+pub fn disk_status(dir: &Path, settings: &RetentionSettings) -> io::Result<DiskStatus> {
+    let free_bytes = free_space(dir)?;
+    let min_free_bytes = settings.min_free_mb * 1024 * 1024;
+    Ok(DiskStatus {
+        dir: dir.to_path_buf(),
+        free_bytes,
+        min_free_bytes,
+        is_low: free_bytes < min_free_bytes,
+    })
+}
+
+fn is_kept(clip: &Clip) -> bool {
+    clip.favorite || clip.play_count > 0 || clip.context.kind == ClipKind::Manual
+}
+
+// お気に入り・再生済み・手動保存を除き、keep_gamesより前の試合のクリップ
+// 別アングルは同じハイライトとしてまとめて判断し、どれか1つでも残すなら全アングルを残す
+pub fn candidates(catalog: &ClipCatalog, current_game: u32, keep_games: u32) -> Vec<Clip> {
+    let kept_highlights: HashSet<u64> = catalog
+        .clips()
+        .iter()
+        .filter(|c| is_kept(c))
+        .filter_map(|c| c.context.highlight_id)
+        .collect();
+    catalog
+        .clips()
+        .iter()
+        .filter(|c| !is_kept(c))
+        .filter(|c| {
+            c.context
+                .highlight_id
+                .is_none_or(|id| !kept_highlights.contains(&id))
+        })
+        .filter(|c| c.context.game_number + keep_games <= current_game)
+        .cloned()
+        .collect()
+}
+
+pub fn apply(
+    catalog: &Mutex<ClipCatalog>,
+    current_game: u32,
+    settings: &RetentionSettings,
+    dry_run: bool,
+) -> RetentionReport {
+    let targets = candidates(&catalog.lock().unwrap(), current_game, settings.keep_games);
+    let mut report = RetentionReport {
+        dry_run,
+        clips: Vec::new(),
+        freed_bytes: 0,
+        errors: Vec::new(),
+    };
+    for clip in targets {
+        let size = std::fs::metadata(&clip.path).map(|m| m.len()).unwrap_or(0);
+        if !dry_run {
+            if let Err(e) = std::fs::remove_file(&clip.path)
+                && e.kind() != io::ErrorKind::NotFound
+            {
+                report
+                    .errors
+                    .push(format!("Failed to remove {:?}: {}", clip.path, e));
+                continue;
+            }
+            if let Some(thumbnail) = &clip.thumbnail {
+                let _ = std::fs::remove_file(thumbnail);
+            }
+            catalog.lock().unwrap().remove(clip.id);
+        }
+        report.freed_bytes += size;
+        report.clips.push(clip);
+    }
+    report
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::clip_catalog::ClipContext;
+    use crate::match_state::MatchState;
+
+    #[test]
+    fn test_candidates() {
+        let mut catalog = ClipCatalog::new();
+        for game_number in 1..=4 {
+            let state = MatchState {
+                game_number,
+                ..MatchState::default()
+            };
+            catalog.push_pending(ClipContext::from_state(ClipKind::Goal, &state));
+            catalog.attach(PathBuf::from(format!("{game_number}.mkv")));
+        }
+        catalog.attach(PathBuf::from("manual.mkv"));
+        catalog.set_favorite(0, true).unwrap();
+        catalog.mark_played(&[PathBuf::from("2.mkv")]);
+
+        let ids: Vec<u64> = candidates(&catalog, 5, 2).iter().map(|c| c.id).collect();
+        assert_eq!(ids, vec![2]);
+    }
+
+    #[test]
+    fn test_candidates_per_highlight() {
+        let mut catalog = ClipCatalog::new();
+        let state = MatchState {
+            game_number: 1,
+            ..MatchState::default()
+        };
+        // 3つのハイライトをメインと別アングルで保存する
+        for _ in 0..3 {
+            let highlight_id = catalog.new_highlight_id();
+            for angle in ["main", "cam2"] {
+                catalog.push_pending(ClipContext {
+                    angle: angle.to_string(),
+                    highlight_id: Some(highlight_id),
+                    ..ClipContext::from_state(ClipKind::Goal, &state)
+                });
+            }
+            for angle in ["main", "cam2"] {
+                catalog.attach_angle(angle, PathBuf::from(format!("{highlight_id}_{angle}.mkv")));
+            }
+        }
+        // 別アングルだけお気に入り、メインだけ再生済み
+        catalog.set_favorite(1, true).unwrap();
+        catalog.mark_played(&[PathBuf::from("1_main.mkv")]);
+
+        let ids: Vec<u64> = candidates(&catalog, 5, 2).iter().map(|c| c.id).collect();
+        assert_eq!(ids, vec![4, 5]);
+    }
+}
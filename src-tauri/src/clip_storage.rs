@@ -0,0 +1,252 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::clip_catalog::Clip;
+use crate::clip_naming::sanitize;
+
+// 大会/シリーズ/試合ごとのフォルダに整理する設定
+// rootがNoneならOBSの保存フォルダのまま
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageSettings {
+    pub root: Option<PathBuf>,
+    pub tournament: String,
+    pub series: String,
+}
+
+impl StorageSettings {
+    // root/大会名/シリーズ名/G{試合番号}_{matchId}
+    // シリーズ名を設定していなければ、チーム名から自動で始まったシリーズ(current_series)の名前を使う
+    pub fn game_dir(&self, clip: &Clip, current_series: Option<&str>) -> Option<PathBuf> {
+        let mut dir = self.root.clone()?;
+        let series = match current_series {
+            Some(name) if self.series.trim().is_empty() => name,
+            _ => self.series.as_str(),
+        };
+        for name in [self.tournament.as_str(), series] {
+            if !name.trim().is_empty() {
+                dir.push(sanitize(name));
+            }
+        }
+        let ctx = &clip.context;
+        match &ctx.match_id {
+            Some(match_id) => dir.push(sanitize(&format!("G{}_{}", ctx.game_number, match_id))),
+            None => dir.push("unsorted"),
+        }
+        Some(dir)
+    }
+}
+
+const VIDEO_EXTENSIONS: [&str; 4] = ["mkv", "mp4", "mov", "flv"];
+
+// フォルダ直下の動画ファイルとサイズ
+pub fn list_videos(dir: &Path) -> Vec<(PathBuf, u64)> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut videos: Vec<(PathBuf, u64)> = entries
+        .filter_map(|e| e.ok())
+        .filter_map(|e| Some((e.path(), e.metadata().ok()?)))
+        .filter(|(path, meta)| {
+            meta.is_file()
+                && path
+                    .extension()
+                    .and_then(|e| e.to_str())
+                    .is_some_and(|e| VIDEO_EXTENSIONS.contains(&e.to_ascii_lowercase().as_str()))
+        })
+        .map(|(path, meta)| (path, meta.len()))
+        .collect();
+    videos.sort();
+    videos
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct RelinkReport {
+    pub relinked: usize,
+    pub missing: Vec<PathBuf>,
+}
+
+// 既存ファイルと衝突したらクリップIDを付ける
+fn unique_path(path: PathBuf, id: u64) -> PathBuf {
+    if !path.exists() {
+        return path;
+    }
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let name = match path.extension() {
+        Some(ext) => format!("{}_{}.{}", stem, id, ext.to_string_lossy()),
+        None => format!("{}_{}", stem, id),
+    };
+    path.with_file_name(name)
+}
+
+// クリップをtargetへ移動し、実際の移動先を返す
+pub fn move_clip(clip: &Clip, target: PathBuf) -> Result<PathBuf, String> {
+    if target == clip.path {
+        return Ok(target);
+    }
+    let target = unique_path(target, clip.id);
+    if let Some(dir) = target.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {:?}: {e}", dir))?;
+    }
+    if let Err(e) = std::fs::rename(&clip.path, &target) {
+        // 別ドライブへの移動はrenameできないのでコピーしてから消す
+        copy_and_remove(&clip.path, &target)
+            .map_err(|_| format!("Failed to move {:?} to {:?}: {e}", clip.path, target))?;
+    }
+    Ok(target)
+}
+
+fn copy_and_remove(from: &Path, to: &Path) -> Result<(), String> {
+    std::fs::copy(from, to).map_err(|e| format!("Failed to copy {:?}: {e}", from))?;
+    std::fs::remove_file(from).map_err(|e| format!("Failed to remove {:?}: {e}", from))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::clip_catalog::{ClipCatalog, ClipContext, ClipKind};
+    use crate::match_state::MatchState;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("clip_storage_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn saved_clip(catalog: &mut ClipCatalog, path: PathBuf, match_id: Option<&str>) -> Clip {
+        let state = MatchState {
+            match_id: match_id.map(String::from),
+            game_number: 3,
+            ..MatchState::default()
+        };
+        catalog.push_pending(ClipContext::from_state(ClipKind::Goal, &state));
+        catalog.attach(path)
+    }
+
+    #[test]
+    fn test_game_dir() {
+        let root = temp_dir("game_dir");
+        let mut catalog = ClipCatalog::new();
+        let clip = saved_clip(&mut catalog, root.join("a.mkv"), Some("AB:C"));
+        let mut settings = StorageSettings {
+            root: Some(root.clone()),
+            tournament: "RLCS 2025".to_string(),
+            series: "Grand/Final".to_string(),
+        };
+        assert_eq!(
+            settings.game_dir(&clip, None),
+            Some(root.join("RLCS 2025").join("Grand_Final").join("G3_AB_C"))
+        );
+        // 設定したシリーズ名は自動で始まったシリーズより優先する
+        assert_eq!(
+            settings.game_dir(&clip, Some("Moca vs Mugi")),
+            Some(root.join("RLCS 2025").join("Grand_Final").join("G3_AB_C"))
+        );
+
+        // 空の大会名・シリーズ名は階層を作らず、試合が分からなければunsortedに入れる
+        settings.series = " ".to_string();
+        let unsorted = saved_clip(&mut catalog, root.join("b.mkv"), None);
+        assert_eq!(
+            settings.game_dir(&unsorted, None),
+            Some(root.join("RLCS 2025").join("unsorted"))
+        );
+        assert_eq!(
+            settings.game_dir(&clip, Some("Moca vs Mugi")),
+            Some(root.join("RLCS 2025").join("Moca vs Mugi").join("G3_AB_C"))
+        );
+
+        settings.root = None;
+        assert_eq!(settings.game_dir(&clip, None), None);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_move_clip_collision() {
+        let root = temp_dir("collision");
+        let mut catalog = ClipCatalog::new();
+        let source = root.join("Replay.mkv");
+        std::fs::write(&source, b"new").unwrap();
+        let clip = saved_clip(&mut catalog, source.clone(), Some("m1"));
+        let target = root.join("G3_m1").join("goal.mkv");
+        std::fs::create_dir_all(target.parent().unwrap()).unwrap();
+        std::fs::write(&target, b"old").unwrap();
+
+        // 既存のファイルは上書きせず、クリップIDを付けた名前にする
+        let moved = move_clip(&clip, target.clone()).unwrap();
+        assert_eq!(
+            moved,
+            root.join("G3_m1").join(format!("goal_{}.mkv", clip.id))
+        );
+        assert_eq!(std::fs::read(&target).unwrap(), b"old");
+        assert_eq!(std::fs::read(&moved).unwrap(), b"new");
+        assert!(!source.exists());
+
+        // 移動先が今の場所なら何もしない
+        let clip = catalog.set_path(clip.id, moved.clone()).unwrap();
+        assert_eq!(move_clip(&clip, moved.clone()).unwrap(), moved);
+        assert!(moved.exists());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_copy_and_remove() {
+        let root = temp_dir("copy");
+        let source = root.join("Replay.mkv");
+        std::fs::write(&source, b"clip").unwrap();
+        let target = root.join("moved.mkv");
+        copy_and_remove(&source, &target).unwrap();
+        assert_eq!(std::fs::read(&target).unwrap(), b"clip");
+        assert!(!source.exists());
+
+        // コピーできなければ元のファイルも消さずにエラーにする
+        assert!(copy_and_remove(&source, &root.join("again.mkv")).is_err());
+        assert!(!root.join("again.mkv").exists());
+
+        let mut catalog = ClipCatalog::new();
+        let clip = saved_clip(&mut catalog, source.clone(), None);
+        let err = move_clip(&clip, root.join("missing.mkv")).unwrap_err();
+        assert!(err.starts_with("Failed to move"), "{err}");
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_relink_missing() {
+        let root = temp_dir("relink");
+        let old_root = root.join("old");
+        let new_root = root.join("new");
+        std::fs::create_dir_all(new_root.join("G3_m1")).unwrap();
+        std::fs::write(new_root.join("G3_m1").join("a.mkv"), b"a").unwrap();
+
+        let mut catalog = ClipCatalog::new();
+        let found = saved_clip(
+            &mut catalog,
+            old_root.join("G3_m1").join("a.mkv"),
+            Some("m1"),
+        );
+        let lost = saved_clip(
+            &mut catalog,
+            old_root.join("G3_m1").join("b.mkv"),
+            Some("m1"),
+        );
+        let outside = saved_clip(&mut catalog, root.join("elsewhere.mkv"), None);
+
+        let report = catalog.relink(&old_root, &new_root);
+        assert_eq!(report.relinked, 1);
+        assert_eq!(report.missing, vec![new_root.join("G3_m1").join("b.mkv")]);
+        // 見つからなかったクリップと対象外のクリップは元のパスのまま
+        assert_eq!(
+            catalog.get(found.id).unwrap().path,
+            new_root.join("G3_m1").join("a.mkv")
+        );
+        assert_eq!(catalog.get(lost.id).unwrap().path, lost.path);
+        assert_eq!(catalog.get(outside.id).unwrap().path, outside.path);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
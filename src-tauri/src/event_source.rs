@@ -0,0 +1,61 @@
+use log::error;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::Sender;
+
+use crate::AppState;
+use crate::stats_api;
+use crate::udp::bind_socket;
+
+// 試合イベントの受信元。どれもMugiのメッセージ({"cmd":..,"data":..})に揃えて
+// トリガー処理のチャネルに流す
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum EventSourceKind {
+    // MugiのUDP(ポート12344)
+    #[default]
+    Mugi,
+    // Rocket League公式のStats API
+    StatsApi,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct EventSourceSettings {
+    pub kind: EventSourceKind,
+    // Stats APIの接続先。ゲームのDefaultStatsAPI.iniのPortに合わせる
+    // host:portならTCP、ws://host:portならWebSocketで接続する
+    pub stats_api_addr: String,
+}
+
+impl Default for EventSourceSettings {
+    fn default() -> Self {
+        Self {
+            kind: EventSourceKind::default(),
+            stats_api_addr: "127.0.0.1:49123".to_string(),
+        }
+    }
+}
+
+// 受信を開始する。切り替えるときは返したタスクをabortしてから呼び直す
+pub fn spawn_source(
+    settings: &EventSourceSettings,
+    tx: Sender<String>,
+    state: &AppState,
+) -> tokio::task::JoinHandle<()> {
+    let capture = state.mugi_capture.clone();
+    match settings.kind {
+        EventSourceKind::Mugi => {
+            let relay = state.relay.clone();
+            let raw = state.mugi_raw.clone();
+            tokio::spawn(async {
+                if let Err(e) = bind_socket(tx, capture, relay, raw).await {
+                    error!("UDP socket error: {}", e);
+                }
+            })
+        }
+        EventSourceKind::StatsApi => {
+            let addr = settings.stats_api_addr.clone();
+            tokio::spawn(stats_api::run(addr, tx, capture))
+        }
+    }
+}
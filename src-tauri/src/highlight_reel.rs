@@ -0,0 +1,302 @@
+use serde::{Deserialize, Serialize};
+
+use crate::clip_catalog::{Clip, ClipCatalog, ClipKind};
+use crate::highlight_score::{ScoredClip, score_clip};
+use crate::series::Series;
+
+// 長さ不明のクリップはOBSリプレイバッファの既定の最大長として扱う
+const ASSUMED_CLIP_DURATION_SEC: f64 = 20.0;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum ReelMode {
+    // 試合終了時に何もしない
+    #[default]
+    Off,
+    // 試合終了時にプレイリストをフロントエンドへ通知
+    Playlist,
+    // 試合終了時にそのまま再生
+    AutoPlay,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ReelSettings {
+    pub mode: ReelMode,
+    pub max_clips: Option<usize>,
+    pub max_duration_sec: Option<f64>,
+    // 入れるセーブの数。スコアの高いセーブから残す
+    pub max_saves: Option<usize>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct HighlightReel {
+    // 試合のリールなら1つ、シリーズのリールなら試合順
+    pub match_ids: Vec<String>,
+    pub series_id: Option<u64>,
+    pub clips: Vec<Clip>,
+    pub total_duration_sec: f64,
+}
+
+impl HighlightReel {
+    pub fn paths(&self) -> Vec<std::path::PathBuf> {
+        self.clips.iter().map(|c| c.path.clone()).collect()
+    }
+}
+
+fn clip_duration(clip: &Clip) -> f64 {
+    clip.playback_duration_sec()
+        .unwrap_or(ASSUMED_CLIP_DURATION_SEC - clip.trim.in_sec.unwrap_or(0.0))
+        .max(0.0)
+}
+
+// 試合のリールを組み立てる
+// 並び順: ゴール(時系列) -> セーブ -> オーバータイムの決勝ゴール
+// 上限を超える場合は決勝ゴール、ゴール、スコアの高いセーブの優先度で残す
+pub fn build_reel(catalog: &ClipCatalog, match_id: &str, settings: &ReelSettings) -> HighlightReel {
+    let clips = catalog.clips_for_match(match_id);
+    let ot_winner = clips
+        .iter()
+        .rev()
+        .find(|c| c.context.kind == ClipKind::Goal && c.context.is_overtime)
+        .map(|c| c.id);
+    let goals = clips
+        .iter()
+        .filter(|c| c.context.kind == ClipKind::Goal && Some(c.id) != ot_winner);
+    let mut saves: Vec<ScoredClip> = clips
+        .iter()
+        .filter(|c| c.context.kind == ClipKind::EpicSave)
+        .map(|c| score_clip(catalog, c))
+        .collect();
+    saves.sort_by(|a, b| b.score.total_cmp(&a.score).then(a.clip.id.cmp(&b.clip.id)));
+    let saves = saves
+        .iter()
+        .map(|s| &s.clip)
+        .take(settings.max_saves.unwrap_or(usize::MAX));
+
+    let by_priority = clips
+        .iter()
+        .copied()
+        .filter(|c| Some(c.id) == ot_winner)
+        .chain(goals.copied())
+        .chain(saves);
+
+    let mut picked: Vec<&Clip> = Vec::new();
+    let mut total_duration_sec = 0.0;
+    for clip in by_priority {
+        if settings.max_clips.is_some_and(|max| picked.len() >= max) {
+            break;
+        }
+        let duration = clip_duration(clip);
+        if settings
+            .max_duration_sec
+            .is_some_and(|max| total_duration_sec + duration > max)
+        {
+            continue;
+        }
+        total_duration_sec += duration;
+        picked.push(clip);
+    }
+
+    // 表示順に並び替え
+    let order = |c: &Clip| match c.context.kind {
+        _ if Some(c.id) == ot_winner => 2,
+        ClipKind::Goal => 0,
+        _ => 1,
+    };
+    picked.sort_by_key(|c| (order(c), c.id));
+
+    HighlightReel {
+        match_ids: vec![match_id.to_string()],
+        series_id: None,
+        clips: picked.into_iter().cloned().collect(),
+        total_duration_sec,
+    }
+}
+
+// シリーズのリールを組み立てる。試合ごとのリールを試合順につなげる
+// 上限はシリーズ全体にかけ、超えた分は後の試合から削る
+pub fn build_series_reel(
+    catalog: &ClipCatalog,
+    series: &Series,
+    settings: &ReelSettings,
+) -> HighlightReel {
+    let per_game = ReelSettings {
+        max_clips: None,
+        max_duration_sec: None,
+        ..settings.clone()
+    };
+    let mut clips = Vec::new();
+    let mut total_duration_sec = 0.0;
+    for match_id in &series.match_ids {
+        for clip in build_reel(catalog, match_id, &per_game).clips {
+            if settings.max_clips.is_some_and(|max| clips.len() >= max) {
+                break;
+            }
+            let duration = clip_duration(&clip);
+            if settings
+                .max_duration_sec
+                .is_some_and(|max| total_duration_sec + duration > max)
+            {
+                continue;
+            }
+            total_duration_sec += duration;
+            clips.push(clip);
+        }
+    }
+    HighlightReel {
+        match_ids: series.match_ids.clone(),
+        series_id: Some(series.id),
+        clips,
+        total_duration_sec,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::clip_catalog::ClipContext;
+    use crate::match_state::MatchState;
+    use std::path::PathBuf;
+
+    fn push(catalog: &mut ClipCatalog, kind: ClipKind, is_overtime: bool) {
+        let state = MatchState {
+            match_id: Some("m1".to_string()),
+            is_overtime,
+            ..MatchState::default()
+        };
+        catalog.push_pending(ClipContext::from_state(kind, &state));
+        catalog.attach(PathBuf::from(format!("{kind:?}.mkv")));
+    }
+
+    #[test]
+    fn test_reel_order() {
+        let mut catalog = ClipCatalog::new();
+        push(&mut catalog, ClipKind::EpicSave, false);
+        push(&mut catalog, ClipKind::Goal, false);
+        push(&mut catalog, ClipKind::Goal, true);
+        push(&mut catalog, ClipKind::Goal, false);
+
+        let reel = build_reel(&catalog, "m1", &ReelSettings::default());
+        let ids: Vec<u64> = reel.clips.iter().map(|c| c.id).collect();
+        assert_eq!(ids, vec![1, 3, 0, 2]);
+    }
+
+    #[test]
+    fn test_reel_limits() {
+        let mut catalog = ClipCatalog::new();
+        push(&mut catalog, ClipKind::EpicSave, false);
+        push(&mut catalog, ClipKind::Goal, false);
+        push(&mut catalog, ClipKind::Goal, true);
+
+        let settings = ReelSettings {
+            max_clips: Some(2),
+            ..ReelSettings::default()
+        };
+        let ids: Vec<u64> = build_reel(&catalog, "m1", &settings)
+            .clips
+            .iter()
+            .map(|c| c.id)
+            .collect();
+        assert_eq!(ids, vec![1, 2]);
+
+        let settings = ReelSettings {
+            max_duration_sec: Some(ASSUMED_CLIP_DURATION_SEC),
+            ..ReelSettings::default()
+        };
+        let ids: Vec<u64> = build_reel(&catalog, "m1", &settings)
+            .clips
+            .iter()
+            .map(|c| c.id)
+            .collect();
+        assert_eq!(ids, vec![2]);
+        assert!(build_reel(&catalog, "other", &settings).clips.is_empty());
+    }
+
+    #[test]
+    fn test_reel_best_saves() {
+        let mut catalog = ClipCatalog::new();
+        push(&mut catalog, ClipKind::Goal, false);
+        // デモの多いセーブほどスコアが高い
+        for demos in [0, 2, 1] {
+            let state = MatchState {
+                match_id: Some("m1".to_string()),
+                play_demos: demos,
+                ..MatchState::default()
+            };
+            catalog.push_pending(ClipContext::from_state(ClipKind::EpicSave, &state));
+            catalog.attach(PathBuf::from(format!("save_{demos}.mkv")));
+        }
+
+        // 残したセーブは時系列のまま並べる
+        let settings = ReelSettings {
+            max_saves: Some(2),
+            ..ReelSettings::default()
+        };
+        let ids: Vec<u64> = build_reel(&catalog, "m1", &settings)
+            .clips
+            .iter()
+            .map(|c| c.id)
+            .collect();
+        assert_eq!(ids, vec![0, 2, 3]);
+
+        // 全体の上限でもスコアの低いセーブから外す
+        let settings = ReelSettings {
+            max_clips: Some(2),
+            ..ReelSettings::default()
+        };
+        let ids: Vec<u64> = build_reel(&catalog, "m1", &settings)
+            .clips
+            .iter()
+            .map(|c| c.id)
+            .collect();
+        assert_eq!(ids, vec![0, 2]);
+    }
+
+    #[test]
+    fn test_reel_with_angles() {
+        let mut catalog = ClipCatalog::new();
+        for (kind, is_overtime) in [
+            (ClipKind::Goal, false),
+            (ClipKind::EpicSave, false),
+            (ClipKind::Goal, true),
+        ] {
+            let state = MatchState {
+                match_id: Some("m1".to_string()),
+                is_overtime,
+                ..MatchState::default()
+            };
+            let context = ClipContext {
+                highlight_id: Some(catalog.new_highlight_id()),
+                ..ClipContext::from_state(kind, &state)
+            };
+            for angle in ["program", "ball_cam"] {
+                catalog.push_pending(ClipContext {
+                    angle: angle.to_string(),
+                    ..context.clone()
+                });
+            }
+        }
+        for i in 0..3 {
+            catalog.attach_angle("program", PathBuf::from(format!("program_{i}.mkv")));
+            catalog.attach_angle("ball_cam", PathBuf::from(format!("ball_cam_{i}.mkv")));
+        }
+
+        // 1つのプレーは1回だけ入り、決勝ゴールは最後のまま
+        let reel = build_reel(&catalog, "m1", &ReelSettings::default());
+        let paths: Vec<PathBuf> = reel.paths();
+        assert_eq!(
+            paths,
+            ["program_0.mkv", "program_1.mkv", "program_2.mkv"].map(PathBuf::from)
+        );
+
+        let top = crate::highlight_score::top_highlights(
+            &catalog,
+            5,
+            &crate::highlight_score::HighlightScope::Event,
+        );
+        let ids: Vec<u64> = top.iter().map(|s| s.clip.id).collect();
+        assert_eq!(ids, vec![4, 0, 2]);
+    }
+}
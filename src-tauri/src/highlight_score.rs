@@ -0,0 +1,192 @@
+use serde::{Deserialize, Serialize};
+
+use crate::clip_catalog::{Clip, ClipCatalog, ClipKind};
+use crate::match_state::Side;
+
+// 残り時間がこれ以下のゴールを終盤のゴールとして扱う
+const LATE_CLOCK_SEC: u32 = 30;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ScoreReason {
+    Goal,
+    EpicSave,
+    GameWinner,
+    Overtime,
+    LateClock,
+    Comeback,
+    Demo,
+    Buildup,
+}
+
+impl ScoreReason {
+    fn points(self) -> f64 {
+        match self {
+            ScoreReason::Goal => 10.0,
+            ScoreReason::EpicSave => 6.0,
+            ScoreReason::GameWinner => 6.0,
+            ScoreReason::Overtime => 8.0,
+            ScoreReason::LateClock => 4.0,
+            ScoreReason::Comeback => 5.0,
+            ScoreReason::Demo => 2.0,
+            ScoreReason::Buildup => 0.5,
+        }
+    }
+}
+
+// 対象範囲: 試合単位、シリーズ(複数試合)単位、大会全体
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum HighlightScope {
+    #[serde(rename_all = "camelCase")]
+    Match {
+        match_id: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    Series {
+        match_ids: Vec<String>,
+    },
+    Event,
+}
+
+impl HighlightScope {
+    fn contains(&self, clip: &Clip) -> bool {
+        match self {
+            HighlightScope::Match { match_id } => {
+                clip.context.match_id.as_deref() == Some(match_id.as_str())
+            }
+            HighlightScope::Series { match_ids } => clip
+                .context
+                .match_id
+                .as_ref()
+                .is_some_and(|id| match_ids.contains(id)),
+            HighlightScope::Event => true,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ScoredClip {
+    pub clip: Clip,
+    pub score: f64,
+    pub reasons: Vec<ScoreReason>,
+}
+
+fn team_goals(blue_goals: u32, orange_goals: u32, side: Side) -> (u32, u32) {
+    match side {
+        Side::Blue => (blue_goals, orange_goals),
+        Side::Orange => (orange_goals, blue_goals),
+    }
+}
+
+pub fn score_clip(catalog: &ClipCatalog, clip: &Clip) -> ScoredClip {
+    let ctx = &clip.context;
+    let mut reasons = Vec::new();
+    match ctx.kind {
+        ClipKind::Goal => reasons.push(ScoreReason::Goal),
+        ClipKind::EpicSave => reasons.push(ScoreReason::EpicSave),
+        ClipKind::Manual => {}
+    }
+
+    if ctx.kind == ClipKind::Goal {
+        if ctx.is_overtime {
+            reasons.push(ScoreReason::Overtime);
+        } else if ctx.clock.is_some_and(|clock| clock <= LATE_CLOCK_SEC) {
+            reasons.push(ScoreReason::LateClock);
+        }
+        if let Some(side) = ctx.team {
+            // ゴール後のスコア
+            let (own, opp) = team_goals(ctx.blue_goals, ctx.orange_goals, side);
+            // ビハインドから同点または逆転
+            if own > 0 && own - 1 < opp && own >= opp {
+                reasons.push(ScoreReason::Comeback);
+            }
+            // 勝利チームの、相手の最終得点を上回ったゴール
+            let result = ctx.match_id.as_deref().and_then(|id| catalog.result(id));
+            if let Some((blue, orange)) = result {
+                let (own_final, opp_final) = team_goals(blue, orange, side);
+                if own_final > opp_final && own == opp_final + 1 {
+                    reasons.push(ScoreReason::GameWinner);
+                }
+            }
+        }
+    }
+
+    let mut score: f64 = reasons.iter().map(|r| r.points()).sum();
+    if ctx.demos > 0 {
+        reasons.push(ScoreReason::Demo);
+        score += ScoreReason::Demo.points() * ctx.demos.min(3) as f64;
+    }
+    if ctx.touches > 0 {
+        reasons.push(ScoreReason::Buildup);
+        score += ScoreReason::Buildup.points() * ctx.touches.min(10) as f64;
+    }
+
+    ScoredClip {
+        clip: clip.clone(),
+        score,
+        reasons,
+    }
+}
+
+// スコアの高い順にn個取り出す
+pub fn top_highlights(catalog: &ClipCatalog, n: usize, scope: &HighlightScope) -> Vec<ScoredClip> {
+    let mut scored: Vec<ScoredClip> = catalog
+        .highlight_clips()
+        .into_iter()
+        .filter(|c| scope.contains(c))
+        .map(|c| score_clip(catalog, c))
+        .collect();
+    scored.sort_by(|a, b| b.score.total_cmp(&a.score).then(a.clip.id.cmp(&b.clip.id)));
+    scored.truncate(n);
+    scored
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::clip_catalog::ClipContext;
+    use crate::match_state::MatchState;
+    use crate::mugi_schema::Goals;
+    use std::path::PathBuf;
+
+    fn push_goal(catalog: &mut ClipCatalog, team: &str, blue: u32, orange: u32, clock: u32) {
+        let state = MatchState {
+            match_id: Some("m1".to_string()),
+            clock: Some(clock),
+            blue_goals: blue,
+            orange_goals: orange,
+            last_goal: Some(Goals {
+                team: team.to_string(),
+                score_id: "scorer".to_string(),
+                assist_id: String::new(),
+            }),
+            ..MatchState::default()
+        };
+        catalog.push_pending(ClipContext::from_state(ClipKind::Goal, &state));
+        catalog.attach(PathBuf::from("goal.mkv"));
+    }
+
+    #[test]
+    fn test_score_reasons() {
+        let mut catalog = ClipCatalog::new();
+        push_goal(&mut catalog, "orange", 0, 1, 200);
+        push_goal(&mut catalog, "blue", 1, 1, 100);
+        push_goal(&mut catalog, "blue", 2, 1, 10);
+        catalog.record_result("m1".to_string(), 2, 1);
+
+        let top = top_highlights(&catalog, 2, &HighlightScope::Event);
+        assert_eq!(top.len(), 2);
+        assert_eq!(top[0].clip.id, 2);
+        assert!(top[0].reasons.contains(&ScoreReason::GameWinner));
+        assert!(top[0].reasons.contains(&ScoreReason::LateClock));
+        assert_eq!(top[1].clip.id, 1);
+        assert!(top[1].reasons.contains(&ScoreReason::Comeback));
+
+        let scope = HighlightScope::Match {
+            match_id: "other".to_string(),
+        };
+        assert!(top_highlights(&catalog, 5, &scope).is_empty());
+    }
+}
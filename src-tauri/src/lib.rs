@@ -1,35 +1,156 @@
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
+mod api_server;
+mod backend;
+mod clip_catalog;
+mod clip_export;
+mod clip_naming;
+mod clip_retention;
+mod clip_storage;
+mod event_source;
+mod highlight_reel;
+mod highlight_score;
+mod match_state;
+mod media_probe;
+mod mugi_capture;
+mod mugi_diagnostics;
 mod mugi_schema;
 mod obs;
+#[cfg(test)]
+mod obs_mock;
+mod obs_targets;
+mod osc;
+mod player_stats;
+mod remote_trigger;
+mod scoreboard;
+mod series;
+mod source_record;
+mod stats_api;
 mod udp;
 mod vlc_manager;
+mod webhook;
 
-use log::{debug, error, info};
+use api_server::{ApiEvents, ApiServer, ApiSettings};
+use backend::{Backends, CaptureTarget, PlaybackBackend, SavedClip};
+use clip_catalog::{
+    AnglePlaybackSettings, Clip, ClipCatalog, ClipContext, ClipKind, Highlight, Trim,
+};
+use clip_export::{ExportRequest, ExportSettings};
+use clip_naming::RenameSettings;
+use clip_retention::{DiskStatus, RetentionReport, RetentionSettings};
+use clip_storage::{RelinkReport, StorageSettings};
+use event_source::EventSourceSettings;
+use highlight_reel::{HighlightReel, ReelMode, ReelSettings};
+use highlight_score::{HighlightScope, ScoredClip};
+use log::{debug, error, info, warn};
+use match_state::MatchState;
+use mugi_capture::CaptureWriter;
+use mugi_diagnostics::{MugiDiagnostics, ProtocolStatus};
 use mugi_schema::MugiCmd;
+use obs_targets::ObsTarget;
+use osc::{OscServer, OscSettings};
+use player_stats::{StatsAggregator, StatsOutputSettings, StatsSnapshot};
+use remote_trigger::RemoteTrigger;
+use scoreboard::{ScoreboardSettings, ScoreboardValues};
+use series::{Series, SeriesManager};
+use source_record::SourceRecordSettings;
 use std::sync::{Arc, Mutex, RwLock};
-use tauri::AppHandle;
+use tauri::{AppHandle, Emitter, Runtime};
 use tauri_plugin_log::{Target, TargetKind};
 use tauri_plugin_updater::UpdaterExt;
 use tokio::sync::mpsc::{self};
-use udp::bind_socket;
+use tokio::sync::watch;
+use udp::{Relay, RelaySettings};
 use vlc_manager::VlcManager;
-
-// 複雑な型を簡素化するためのtype alias
-type ObsConnectionInfo = Arc<Mutex<Option<(String, u16, Option<String>)>>>;
+use webhook::{WebhookDelivery, WebhookLog, WebhookSettings};
 
 // グローバル状態管理用の構造体
+#[derive(Clone)]
 struct AppState {
-    obs_connection_info: ObsConnectionInfo,
+    // 接続中のOBS
+    obs_targets: Arc<Mutex<Vec<ObsTarget>>>,
     is_system_running: Arc<Mutex<bool>>,
     sleep_duration_sec: Arc<RwLock<u64>>,
+    clip_catalog: Arc<Mutex<ClipCatalog>>,
+    match_state: Arc<Mutex<MatchState>>,
+    reel_settings: Arc<RwLock<ReelSettings>>,
+    export_settings: Arc<RwLock<ExportSettings>>,
+    rename_settings: Arc<RwLock<RenameSettings>>,
+    storage_settings: Arc<RwLock<StorageSettings>>,
+    retention_settings: Arc<RwLock<RetentionSettings>>,
+    // キャプチャ中のUDP受信の書き出し先
+    mugi_capture: Arc<Mutex<Option<CaptureWriter>>>,
+    // 動作中のUDP受信チャネル(キャプチャの再生に使う)
+    mugi_tx: Arc<Mutex<Option<mpsc::Sender<String>>>>,
+    // 動作中の外部トリガーのチャネル
+    trigger_tx: Arc<Mutex<Option<mpsc::Sender<RemoteTrigger>>>>,
+    // 受信したデータグラムの転送先
+    relay_settings: Arc<RwLock<RelaySettings>>,
+    relay: Arc<RwLock<Relay>>,
+    mugi_raw: tokio::sync::broadcast::Sender<String>,
+    event_source_settings: Arc<RwLock<EventSourceSettings>>,
+    // 動作中の受信タスク
+    event_source: Arc<Mutex<Option<tokio::task::JoinHandle<()>>>>,
+    webhook_settings: Arc<RwLock<WebhookSettings>>,
+    webhook_log: Arc<Mutex<WebhookLog>>,
+    source_record_settings: Arc<RwLock<SourceRecordSettings>>,
+    angle_playback_settings: Arc<RwLock<AnglePlaybackSettings>>,
+    mugi_diagnostics: Arc<Mutex<MugiDiagnostics>>,
+    player_stats: Arc<Mutex<StatsAggregator>>,
+    stats_output: Arc<RwLock<StatsOutputSettings>>,
+    scoreboard_settings: Arc<RwLock<ScoreboardSettings>>,
+    series: Arc<Mutex<SeriesManager>>,
+    // スコアボードに流す最新の値
+    scoreboard_tx: Arc<watch::Sender<ScoreboardValues>>,
+    // 動作中の録画・再生先
+    backends: Arc<Mutex<Option<Backends>>>,
+    // 再生と停止を受け付けた順に処理する
+    playback_order: Arc<tokio::sync::Mutex<()>>,
+    api_settings: Arc<RwLock<ApiSettings>>,
+    api_server: Arc<Mutex<Option<ApiServer>>>,
+    osc_settings: Arc<RwLock<OscSettings>>,
+    osc_server: Arc<Mutex<Option<OscServer>>>,
+    // リモート操作のWebSocketに流すイベント
+    api_events: ApiEvents,
 }
 
 impl AppState {
     fn new() -> Self {
         Self {
-            obs_connection_info: Arc::new(Mutex::new(None)),
+            obs_targets: Arc::new(Mutex::new(Vec::new())),
             is_system_running: Arc::new(Mutex::new(false)),
             sleep_duration_sec: Arc::new(RwLock::new(3)), // デフォルト3秒
+            clip_catalog: Arc::new(Mutex::new(ClipCatalog::new())),
+            match_state: Arc::new(Mutex::new(MatchState::new())),
+            reel_settings: Arc::new(RwLock::new(ReelSettings::default())),
+            export_settings: Arc::new(RwLock::new(ExportSettings::default())),
+            rename_settings: Arc::new(RwLock::new(RenameSettings::default())),
+            storage_settings: Arc::new(RwLock::new(StorageSettings::default())),
+            retention_settings: Arc::new(RwLock::new(RetentionSettings::default())),
+            mugi_capture: Arc::new(Mutex::new(None)),
+            mugi_tx: Arc::new(Mutex::new(None)),
+            trigger_tx: Arc::new(Mutex::new(None)),
+            relay_settings: Arc::new(RwLock::new(RelaySettings::default())),
+            relay: Arc::new(RwLock::new(Relay::default())),
+            mugi_raw: tokio::sync::broadcast::channel(256).0,
+            event_source_settings: Arc::new(RwLock::new(EventSourceSettings::default())),
+            event_source: Arc::new(Mutex::new(None)),
+            webhook_settings: Arc::new(RwLock::new(WebhookSettings::default())),
+            webhook_log: Arc::new(Mutex::new(WebhookLog::new())),
+            source_record_settings: Arc::new(RwLock::new(SourceRecordSettings::default())),
+            angle_playback_settings: Arc::new(RwLock::new(AnglePlaybackSettings::default())),
+            mugi_diagnostics: Arc::new(Mutex::new(MugiDiagnostics::new())),
+            player_stats: Arc::new(Mutex::new(StatsAggregator::new())),
+            stats_output: Arc::new(RwLock::new(StatsOutputSettings::default())),
+            scoreboard_settings: Arc::new(RwLock::new(ScoreboardSettings::default())),
+            series: Arc::new(Mutex::new(SeriesManager::new())),
+            scoreboard_tx: Arc::new(watch::Sender::new(ScoreboardValues::default())),
+            backends: Arc::new(Mutex::new(None)),
+            playback_order: Arc::new(tokio::sync::Mutex::new(())),
+            api_settings: Arc::new(RwLock::new(ApiSettings::default())),
+            api_server: Arc::new(Mutex::new(None)),
+            osc_settings: Arc::new(RwLock::new(OscSettings::default())),
+            osc_server: Arc::new(Mutex::new(None)),
+            api_events: tokio::sync::broadcast::channel(64).0,
         }
     }
 }
@@ -58,6 +179,50 @@ async fn set_sleep_duration(
     ))
 }
 
+#[tauri::command]
+async fn get_reel_settings(state: tauri::State<'_, AppState>) -> Result<ReelSettings, String> {
+    let settings = state.reel_settings.read().unwrap();
+    Ok(settings.clone())
+}
+
+#[tauri::command]
+async fn set_reel_settings(
+    settings: ReelSettings,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    *state.reel_settings.write().unwrap() = settings;
+    Ok("ハイライトリールの設定を更新しました".to_string())
+}
+
+// match_id省略時は現在(直前)の試合のリールを組み立てる
+#[tauri::command]
+async fn build_highlight_reel(
+    match_id: Option<String>,
+    state: tauri::State<'_, AppState>,
+) -> Result<HighlightReel, String> {
+    let match_id = match match_id {
+        Some(id) => id,
+        None => match state.match_state.lock().unwrap().match_id.clone() {
+            Some(id) => id,
+            None => return Err("試合IDが見つかりません".to_string()),
+        },
+    };
+    let settings = state.reel_settings.read().unwrap().clone();
+    let catalog = state.clip_catalog.lock().unwrap();
+    Ok(highlight_reel::build_reel(&catalog, &match_id, &settings))
+}
+
+// 指定範囲のクリップをスコア順にn個返す
+#[tauri::command]
+async fn top_highlights(
+    n: usize,
+    scope: HighlightScope,
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<ScoredClip>, String> {
+    let catalog = state.clip_catalog.lock().unwrap();
+    Ok(highlight_score::top_highlights(&catalog, n, &scope))
+}
+
 #[tauri::command]
 async fn play_highlights(
     video_paths: Vec<String>,
@@ -67,37 +232,742 @@ async fn play_highlights(
         return Ok("再生する動画がありません".to_string());
     }
 
-    // OBS接続情報を取得
-    let (host, port, password) = {
-        let conn_info = state.obs_connection_info.lock().unwrap();
-        match conn_info.as_ref() {
-            Some((host, port, password)) => (host.clone(), *port, password.clone()),
-            None => return Err("OBS接続情報が見つかりません".to_string()),
-        }
-    };
-
-    // OBS接続を作成
-    let mut obs = obs::Obs::new();
-    let password_ref = password.as_deref();
-    obs.connect(&host, port, password_ref)
-        .await
-        .map_err(|e| format!("Failed to connect to OBS: {}", e))?;
-
     // ファイル名からPathBufに変換（仮想的なパスとして扱う）
     let movie_pathes: Vec<std::path::PathBuf> =
         video_paths.iter().map(std::path::PathBuf::from).collect();
+    let count = play_paths(&state, movie_pathes).await?;
+
+    Ok(format!("{}個のハイライト動画を再生しました", count))
+}
+
+// ハイライトごとに各アングルを続けて再生する。angles省略時は設定の順番
+#[tauri::command]
+async fn play_highlight_angles(
+    highlight_ids: Vec<u64>,
+    angles: Option<Vec<String>>,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    let order =
+        angles.unwrap_or_else(|| state.angle_playback_settings.read().unwrap().order.clone());
+    let paths = {
+        let catalog = state.clip_catalog.lock().unwrap();
+        highlight_paths(&catalog, &highlight_ids, &order)?
+    };
+    let count = play_paths(&state, paths).await?;
+    Ok(format!(
+        "{}個のハイライトを{}本のクリップで再生しました",
+        highlight_ids.len(),
+        count
+    ))
+}
+
+#[tauri::command]
+async fn list_highlights(state: tauri::State<'_, AppState>) -> Result<Vec<Highlight>, String> {
+    Ok(state.clip_catalog.lock().unwrap().highlights())
+}
+
+#[tauri::command]
+async fn get_angle_playback_settings(
+    state: tauri::State<'_, AppState>,
+) -> Result<AnglePlaybackSettings, String> {
+    let settings = state.angle_playback_settings.read().unwrap();
+    Ok(settings.clone())
+}
+
+#[tauri::command]
+async fn set_angle_playback_settings(
+    settings: AnglePlaybackSettings,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    *state.angle_playback_settings.write().unwrap() = settings;
+    Ok("アングルの再生順を保存しました".to_string())
+}
+
+#[tauri::command]
+async fn stop_playback(state: tauri::State<'_, AppState>) -> Result<String, String> {
+    stop_current_playback(&state).await?;
+    Ok("再生を停止しました".to_string())
+}
+
+// 録画遅延を待たずに今のリプレイを保存する
+#[tauri::command]
+async fn save_clip_now(state: tauri::State<'_, AppState>) -> Result<String, String> {
+    save_clip(&state, ClipKind::Manual)?;
+    Ok("リプレイを保存しています".to_string())
+}
+
+#[tauri::command]
+async fn list_clips(state: tauri::State<'_, AppState>) -> Result<Vec<Clip>, String> {
+    let catalog = state.clip_catalog.lock().unwrap();
+    Ok(catalog.clips().to_vec())
+}
+
+// 各録画先の保存フォルダにあるクリップ。カタログに無い(起動前に保存された)ものも返す
+#[tauri::command]
+async fn list_saved_clips(state: tauri::State<'_, AppState>) -> Result<Vec<SavedClip>, String> {
+    let capture = match state.backends.lock().unwrap().as_ref() {
+        Some(backends) => backends.capture.clone(),
+        None => return Err("システムが起動していません".to_string()),
+    };
+    let mut clips = Vec::new();
+    for target in capture {
+        let paths = target
+            .backend
+            .list_saved_clips()
+            .await
+            .map_err(|e| format!("{}: {}", target.name, e))?;
+        clips.extend(paths.into_iter().map(|path| SavedClip {
+            angle: target.name.clone(),
+            path,
+        }));
+    }
+    Ok(clips)
+}
+
+#[tauri::command]
+async fn get_status(state: tauri::State<'_, AppState>) -> Result<SystemStatus, String> {
+    Ok(system_status(&state))
+}
+
+#[tauri::command]
+async fn get_api_settings(state: tauri::State<'_, AppState>) -> Result<ApiSettings, String> {
+    let settings = state.api_settings.read().unwrap();
+    Ok(settings.clone())
+}
+
+// 設定を保存し、リモート操作のサーバーを起動し直す
+#[tauri::command]
+async fn set_api_settings(
+    settings: ApiSettings,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    if let Some(server) = state.api_server.lock().unwrap().take() {
+        server.stop();
+    }
+    *state.api_settings.write().unwrap() = settings.clone();
+    if !settings.enabled {
+        return Ok("リモート操作を停止しました".to_string());
+    }
+    let server = api_server::start(&settings, state.inner().clone()).await?;
+    let port = server.addr.port();
+    *state.api_server.lock().unwrap() = Some(server);
+    Ok(format!("リモート操作をポート{}で開始しました", port))
+}
+
+#[tauri::command]
+async fn get_event_source_settings(
+    state: tauri::State<'_, AppState>,
+) -> Result<EventSourceSettings, String> {
+    let settings = state.event_source_settings.read().unwrap();
+    Ok(settings.clone())
+}
+
+// 受信元を切り替える。システムが動作中なら受信し直す
+#[tauri::command]
+async fn set_event_source_settings(
+    settings: EventSourceSettings,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    *state.event_source_settings.write().unwrap() = settings.clone();
+    let Some(tx) = state.mugi_tx.lock().unwrap().clone() else {
+        return Ok("受信元を保存しました".to_string());
+    };
+    if let Some(source) = state.event_source.lock().unwrap().take() {
+        source.abort();
+    }
+    let source = event_source::spawn_source(&settings, tx, &state);
+    *state.event_source.lock().unwrap() = Some(source);
+    Ok(format!("受信元を{:?}に切り替えました", settings.kind))
+}
+
+#[tauri::command]
+async fn get_webhook_settings(
+    state: tauri::State<'_, AppState>,
+) -> Result<WebhookSettings, String> {
+    let settings = state.webhook_settings.read().unwrap();
+    Ok(settings.clone())
+}
+
+#[tauri::command]
+async fn set_webhook_settings(
+    settings: WebhookSettings,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    if let Some(target) = settings
+        .targets
+        .iter()
+        .find(|t| !t.url.starts_with("http://") && !t.url.starts_with("https://"))
+    {
+        return Err(format!("{}はhttp(s)のURLではありません", target.url));
+    }
+    *state.webhook_settings.write().unwrap() = settings;
+    Ok("Webhookの設定を保存しました".to_string())
+}
+
+// 新しい順の送信履歴
+#[tauri::command]
+async fn get_webhook_deliveries(
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<WebhookDelivery>, String> {
+    Ok(state.webhook_log.lock().unwrap().entries())
+}
+
+#[tauri::command]
+async fn get_source_record_settings(
+    state: tauri::State<'_, AppState>,
+) -> Result<SourceRecordSettings, String> {
+    let settings = state.source_record_settings.read().unwrap();
+    Ok(settings.clone())
+}
+
+// 録画先はOBSに接続するときに作るので、次の接続から反映される
+#[tauri::command]
+async fn set_source_record_settings(
+    settings: SourceRecordSettings,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    settings.validate()?;
+    *state.source_record_settings.write().unwrap() = settings;
+    Ok("Source Recordの設定を保存しました。次のOBS接続から反映されます".to_string())
+}
+
+#[tauri::command]
+async fn get_relay_settings(state: tauri::State<'_, AppState>) -> Result<RelaySettings, String> {
+    let settings = state.relay_settings.read().unwrap();
+    Ok(settings.clone())
+}
+
+// 転送先を名前解決してから切り替える。受信中でもすぐに反映される
+#[tauri::command]
+async fn set_relay_settings(
+    settings: RelaySettings,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    let relay = Relay::resolve(&settings).await?;
+    let count = relay.targets.len();
+    *state.relay.write().unwrap() = relay;
+    *state.relay_settings.write().unwrap() = settings;
+    Ok(format!("{}件の転送先を設定しました", count))
+}
+
+#[tauri::command]
+async fn get_osc_settings(state: tauri::State<'_, AppState>) -> Result<OscSettings, String> {
+    let settings = state.osc_settings.read().unwrap();
+    Ok(settings.clone())
+}
+
+// 設定を保存し、OSCの待ち受けを起動し直す
+#[tauri::command]
+async fn set_osc_settings(
+    settings: OscSettings,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    if let Some(server) = state.osc_server.lock().unwrap().take() {
+        server.stop();
+    }
+    *state.osc_settings.write().unwrap() = settings.clone();
+    if !settings.enabled {
+        return Ok("OSCの受信を停止しました".to_string());
+    }
+    let server = osc::start(&settings, state.inner().clone()).await?;
+    let port = server.addr.port();
+    *state.osc_server.lock().unwrap() = Some(server);
+    Ok(format!("OSCをポート{}で受信しています", port))
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SystemStatus {
+    is_system_running: bool,
+    match_state: MatchState,
+    series: Option<Series>,
+    clip_count: usize,
+    protocol: ProtocolStatus,
+}
+
+fn system_status(state: &AppState) -> SystemStatus {
+    SystemStatus {
+        is_system_running: *state.is_system_running.lock().unwrap(),
+        match_state: state.match_state.lock().unwrap().clone(),
+        series: state.series.lock().unwrap().current().cloned(),
+        clip_count: state.clip_catalog.lock().unwrap().clips().len(),
+        protocol: state.mugi_diagnostics.lock().unwrap().protocol.clone(),
+    }
+}
+
+// 動作中の再生先を取得
+fn current_playback(state: &AppState) -> Result<Arc<dyn PlaybackBackend>, String> {
+    match state.backends.lock().unwrap().as_ref() {
+        Some(backends) => Ok(backends.playback.clone()),
+        None => Err("システムが起動していません".to_string()),
+    }
+}
+
+// 指定したクリップを再生する。Tauriコマンドとリモート操作の共通処理
+async fn play_paths(state: &AppState, paths: Vec<std::path::PathBuf>) -> Result<usize, String> {
+    let playback = current_playback(state)?;
+    let _order = state.playback_order.lock().await;
+    play_with(state, playback.as_ref(), paths).await
+}
+
+// 再生を別タスクで始めてすぐに返す(リモート操作のボタン用)
+// 直後に届いた停止が先に処理されないよう、順番だけは返す前に確保する
+async fn spawn_play_paths(
+    state: &AppState,
+    paths: Vec<std::path::PathBuf>,
+) -> Result<usize, String> {
+    let playback = current_playback(state)?;
+    let order = state.playback_order.clone().lock_owned().await;
+    let count = paths.len();
+    let state = state.clone();
+    tokio::spawn(async move {
+        let _order = order;
+        if let Err(e) = play_with(&state, playback.as_ref(), paths).await {
+            error!("{}", e);
+        }
+    });
+    Ok(count)
+}
+
+// playback_startedで流す内容。カタログにあるクリップは試合状況付きで送る
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PlaybackStarted {
+    paths: Vec<std::path::PathBuf>,
+    clips: Vec<Clip>,
+}
+
+async fn play_with(
+    state: &AppState,
+    playback: &dyn PlaybackBackend,
+    paths: Vec<std::path::PathBuf>,
+) -> Result<usize, String> {
+    let (items, clips) = {
+        let mut catalog = state.clip_catalog.lock().unwrap();
+        catalog.mark_played(&paths);
+        let clips: Vec<Clip> = paths
+            .iter()
+            .filter_map(|path| catalog.find_by_path(path).cloned())
+            .collect();
+        (playback_items(&catalog, &paths), clips)
+    };
 
     // VLCソースで動画再生
-    if let Err(e) = obs.play_vlc_source(&movie_pathes).await {
+    if let Err(e) = playback.play_playlist(&items).await {
+        api_server::publish(&state.api_events, "playback_failed", &e);
         return Err(format!("Failed to play VLC source: {}", e));
     }
+    let count = paths.len();
+    api_server::publish(
+        &state.api_events,
+        "playback_started",
+        &PlaybackStarted { paths, clips },
+    );
+    Ok(count)
+}
 
+async fn stop_current_playback(state: &AppState) -> Result<(), String> {
+    let playback = current_playback(state)?;
+    let _order = state.playback_order.lock().await;
+    playback.stop().await?;
+    api_server::publish(&state.api_events, "playback_stopped", &());
+    Ok(())
+}
+
+fn save_clip(state: &AppState, kind: ClipKind) -> Result<(), String> {
+    let capture = match state.backends.lock().unwrap().as_ref() {
+        Some(backends) => backends.capture.clone(),
+        None => return Err("システムが起動していません".to_string()),
+    };
+    spawn_save_replay(kind, None, capture, state.clone());
+    Ok(())
+}
+
+// クリップのin/out点を設定する(Noneで解除)
+#[tauri::command]
+async fn set_clip_trim(
+    clip_id: u64,
+    in_sec: Option<f64>,
+    out_sec: Option<f64>,
+    state: tauri::State<'_, AppState>,
+) -> Result<Clip, String> {
+    let mut catalog = state.clip_catalog.lock().unwrap();
+    catalog.set_trim(clip_id, Trim { in_sec, out_sec })
+}
+
+#[tauri::command]
+async fn get_export_settings(state: tauri::State<'_, AppState>) -> Result<ExportSettings, String> {
+    let settings = state.export_settings.read().unwrap();
+    Ok(settings.clone())
+}
+
+#[tauri::command]
+async fn set_export_settings(
+    settings: ExportSettings,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    *state.export_settings.write().unwrap() = settings;
+    Ok("書き出し設定を更新しました".to_string())
+}
+
+#[tauri::command]
+async fn get_rename_settings(state: tauri::State<'_, AppState>) -> Result<RenameSettings, String> {
+    let settings = state.rename_settings.read().unwrap();
+    Ok(settings.clone())
+}
+
+#[tauri::command]
+async fn set_rename_settings(
+    settings: RenameSettings,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    *state.rename_settings.write().unwrap() = settings;
+    Ok("リネーム設定を更新しました".to_string())
+}
+
+#[tauri::command]
+async fn get_storage_settings(
+    state: tauri::State<'_, AppState>,
+) -> Result<StorageSettings, String> {
+    let settings = state.storage_settings.read().unwrap();
+    Ok(settings.clone())
+}
+
+#[tauri::command]
+async fn set_storage_settings(
+    settings: StorageSettings,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    *state.storage_settings.write().unwrap() = settings;
+    Ok("保存フォルダ設定を更新しました".to_string())
+}
+
+// 保存フォルダを移動した後、カタログのパスを新しいフォルダに付け替える
+#[tauri::command]
+async fn relink_clips(
+    old_root: std::path::PathBuf,
+    new_root: std::path::PathBuf,
+    state: tauri::State<'_, AppState>,
+) -> Result<RelinkReport, String> {
+    if !new_root.is_dir() {
+        return Err(format!("{:?}が見つかりません", new_root));
+    }
+    let report = state
+        .clip_catalog
+        .lock()
+        .unwrap()
+        .relink(&old_root, &new_root);
+    {
+        let mut storage = state.storage_settings.write().unwrap();
+        if storage.root.as_deref() == Some(old_root.as_path()) {
+            storage.root = Some(new_root);
+        }
+    }
+    info!(
+        "Relinked {} clips ({} missing)",
+        report.relinked,
+        report.missing.len()
+    );
+    Ok(report)
+}
+
+#[tauri::command]
+async fn get_retention_settings(
+    state: tauri::State<'_, AppState>,
+) -> Result<RetentionSettings, String> {
+    let settings = state.retention_settings.read().unwrap();
+    Ok(settings.clone())
+}
+
+#[tauri::command]
+async fn set_retention_settings(
+    settings: RetentionSettings,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    *state.retention_settings.write().unwrap() = settings;
+    Ok("保持ポリシーを更新しました".to_string())
+}
+
+#[tauri::command]
+async fn get_disk_status(state: tauri::State<'_, AppState>) -> Result<DiskStatus, String> {
+    let dir = {
+        let catalog = state.clip_catalog.lock().unwrap();
+        let storage = state.storage_settings.read().unwrap();
+        clip_retention::watch_dir(&catalog, &storage)
+    };
+    let Some(dir) = dir else {
+        return Err("監視するフォルダがありません".to_string());
+    };
+    let settings = state.retention_settings.read().unwrap().clone();
+    clip_retention::disk_status(&dir, &settings).map_err(|e| e.to_string())
+}
+
+// 保持ポリシーを適用する。dry_runなら削除対象を返すだけ
+#[tauri::command]
+async fn apply_retention(
+    dry_run: bool,
+    state: tauri::State<'_, AppState>,
+) -> Result<RetentionReport, String> {
+    let current_game = state.match_state.lock().unwrap().game_number;
+    let settings = state.retention_settings.read().unwrap().clone();
+    Ok(clip_retention::apply(
+        &state.clip_catalog,
+        current_game,
+        &settings,
+        dry_run,
+    ))
+}
+
+#[tauri::command]
+async fn set_clip_favorite(
+    clip_id: u64,
+    favorite: bool,
+    state: tauri::State<'_, AppState>,
+) -> Result<Clip, String> {
+    let mut catalog = state.clip_catalog.lock().unwrap();
+    catalog.set_favorite(clip_id, favorite)
+}
+
+// 受信したMugiのUDPをJSONLに記録する
+#[tauri::command]
+async fn start_capture(
+    path: std::path::PathBuf,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    let writer = CaptureWriter::create(&path).map_err(|e| e.to_string())?;
+    *state.mugi_capture.lock().unwrap() = Some(writer);
+    info!("Capturing Mugi messages to {:?}", path);
+    Ok(format!("{:?}に記録を開始しました", path))
+}
+
+#[tauri::command]
+async fn stop_capture(state: tauri::State<'_, AppState>) -> Result<String, String> {
+    match state.mugi_capture.lock().unwrap().take() {
+        Some(_) => Ok("記録を停止しました".to_string()),
+        None => Err("記録していません".to_string()),
+    }
+}
+
+// 記録したキャプチャを受信処理に流す。speedは再生倍率(0以下で待たずに流す)
+#[tauri::command]
+async fn replay_capture(
+    path: std::path::PathBuf,
+    speed: f64,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    let Some(tx) = state.mugi_tx.lock().unwrap().clone() else {
+        return Err("システムが起動していません".to_string());
+    };
+    let records = mugi_capture::read_capture(&path).map_err(|e| e.to_string())?;
+    let count = records.len();
+    tokio::spawn(async move {
+        if let Err(e) = mugi_capture::replay(&records, speed, &tx).await {
+            error!("Failed to replay capture: {}", e);
+        }
+        info!("Replayed {} Mugi messages", records.len());
+    });
+    Ok(format!("{}件のメッセージを再生します", count))
+}
+
+// Mugiの受信状況(プロトコルのバージョン、未対応のコマンド)を返す
+#[tauri::command]
+async fn get_mugi_diagnostics(
+    state: tauri::State<'_, AppState>,
+) -> Result<MugiDiagnostics, String> {
+    let diagnostics = state.mugi_diagnostics.lock().unwrap();
+    Ok(diagnostics.clone())
+}
+
+// 現在の試合とシリーズのプレイヤー・チームの成績を返す
+#[tauri::command]
+async fn get_stats(state: tauri::State<'_, AppState>) -> Result<StatsSnapshot, String> {
+    let stats = state.player_stats.lock().unwrap();
+    Ok(stats.snapshot())
+}
+
+#[tauri::command]
+async fn reset_series_stats(state: tauri::State<'_, AppState>) -> Result<String, String> {
+    state.player_stats.lock().unwrap().reset_series();
+    Ok("シリーズの成績をリセットしました".to_string())
+}
+
+#[tauri::command]
+async fn get_stats_output_settings(
+    state: tauri::State<'_, AppState>,
+) -> Result<StatsOutputSettings, String> {
+    let settings = state.stats_output.read().unwrap();
+    Ok(settings.clone())
+}
+
+#[tauri::command]
+async fn set_stats_output_settings(
+    settings: StatsOutputSettings,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    *state.stats_output.write().unwrap() = settings;
+    Ok("成績ファイルの設定を更新しました".to_string())
+}
+
+#[tauri::command]
+async fn get_scoreboard_settings(
+    state: tauri::State<'_, AppState>,
+) -> Result<ScoreboardSettings, String> {
+    let settings = state.scoreboard_settings.read().unwrap();
+    Ok(settings.clone())
+}
+
+#[tauri::command]
+async fn set_scoreboard_settings(
+    settings: ScoreboardSettings,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    *state.scoreboard_settings.write().unwrap() = settings;
+    // 追加されたソースにも現在の値を送る
+    state.scoreboard_tx.send_modify(|_| {});
+    Ok("スコアボードの設定を更新しました".to_string())
+}
+
+#[tauri::command]
+async fn get_series(state: tauri::State<'_, AppState>) -> Result<Option<Series>, String> {
+    let series = state.series.lock().unwrap();
+    Ok(series.current().cloned())
+}
+
+#[tauri::command]
+async fn list_series(state: tauri::State<'_, AppState>) -> Result<Vec<Series>, String> {
+    let series = state.series.lock().unwrap();
+    Ok(series.list())
+}
+
+// 現在のシリーズを終えて新しいシリーズを開始する。以降の試合はチーム名に関係なくこのシリーズに入る
+#[tauri::command]
+async fn start_series(
+    name: Option<String>,
+    best_of: Option<u32>,
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<Series, String> {
+    let series = state.series.lock().unwrap().start(name, best_of).clone();
+    info!("Series {} started: {}", series.id, series.name);
+    begin_series(&state);
+    notify_series(&series, &state, &app_handle);
+    Ok(series)
+}
+
+#[tauri::command]
+async fn end_series(
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<Series, String> {
+    let Some(series) = state.series.lock().unwrap().end() else {
+        return Err("シリーズが開始されていません".to_string());
+    };
+    info!("Series {} ended", series.id);
+    notify_series(&series, &state, &app_handle);
+    Ok(series)
+}
+
+// series_id省略時は現在(直前)のシリーズ
+fn find_series(state: &AppState, series_id: Option<u64>) -> Result<Series, String> {
+    let manager = state.series.lock().unwrap();
+    let series = match series_id {
+        Some(id) => manager.get(id),
+        None => manager.current(),
+    };
+    series
+        .cloned()
+        .ok_or_else(|| "シリーズが見つかりません".to_string())
+}
+
+#[tauri::command]
+async fn build_series_reel(
+    series_id: Option<u64>,
+    state: tauri::State<'_, AppState>,
+) -> Result<HighlightReel, String> {
+    let series = find_series(&state, series_id)?;
+    let settings = state.reel_settings.read().unwrap().clone();
+    let catalog = state.clip_catalog.lock().unwrap();
+    Ok(highlight_reel::build_series_reel(
+        &catalog, &series, &settings,
+    ))
+}
+
+// シリーズのクリップをスコア順にn個返す
+#[tauri::command]
+async fn series_highlights(
+    series_id: Option<u64>,
+    n: usize,
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<ScoredClip>, String> {
+    let series = find_series(&state, series_id)?;
+    let scope = HighlightScope::Series {
+        match_ids: series.match_ids,
+    };
+    let catalog = state.clip_catalog.lock().unwrap();
+    Ok(highlight_score::top_highlights(&catalog, n, &scope))
+}
+
+// クリップを1本の動画に書き出す。進捗はexport_progressイベントで通知
+#[tauri::command]
+async fn export_highlights(
+    request: ExportRequest,
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<String, String> {
+    let job = {
+        let catalog = state.clip_catalog.lock().unwrap();
+        clip_export::build_job(&catalog, &request)?
+    };
+    let settings = state.export_settings.read().unwrap().clone();
+    clip_export::spawn_export(settings, job, app_handle);
     Ok(format!(
-        "{}個のハイライト動画を再生しました",
-        video_paths.len()
+        "{}個のクリップの書き出しを開始しました",
+        request.clip_ids.len()
     ))
 }
 
+// ハイライトを順に、それぞれのアングルをorderの順に並べたプレイリスト
+fn highlight_paths(
+    catalog: &ClipCatalog,
+    highlight_ids: &[u64],
+    order: &[String],
+) -> Result<Vec<std::path::PathBuf>, String> {
+    let mut paths = Vec::new();
+    for &id in highlight_ids {
+        let highlight = catalog
+            .highlight(id)
+            .ok_or_else(|| format!("ハイライト{}が見つかりません", id))?;
+        paths.extend(
+            highlight
+                .playlist(order)
+                .into_iter()
+                .map(|a| a.path.clone()),
+        );
+    }
+    if paths.is_empty() {
+        return Err("再生できるアングルがありません".to_string());
+    }
+    Ok(paths)
+}
+
+// カタログに登録されたクリップはin/out点を付けて再生する
+fn playback_items(catalog: &ClipCatalog, paths: &[std::path::PathBuf]) -> Vec<obs::PlaybackItem> {
+    paths
+        .iter()
+        .map(|path| {
+            let trim = catalog
+                .find_by_path(path)
+                .map(|clip| clip.trim)
+                .unwrap_or_default();
+            obs::PlaybackItem {
+                path: path.clone(),
+                in_sec: trim.in_sec,
+                out_sec: trim.out_sec,
+            }
+        })
+        .collect()
+}
+
 #[tauri::command]
 async fn connect_obs(
     host: String,
@@ -107,7 +977,18 @@ async fn connect_obs(
     app_handle: tauri::AppHandle,
 ) -> Result<String, String> {
     info!("Attempting to connect to OBS at {}:{}", host, port);
+    let targets = vec![ObsTarget::single(host, port, password)];
+    connect_obs_targets(targets, state, app_handle).await?;
+    Ok("OBS接続に成功しました".to_string())
+}
 
+// 役割付きの複数のOBSに接続してシステムを開始する
+#[tauri::command]
+async fn connect_obs_targets(
+    targets: Vec<ObsTarget>,
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<String, String> {
     // 既にシステムが動作中の場合はエラー
     {
         let is_running = state.is_system_running.lock().unwrap();
@@ -115,47 +996,37 @@ async fn connect_obs(
             return Err("システムは既に動作中です".to_string());
         }
     }
+    obs_targets::validate(&targets)?;
 
-    let mut obs = obs::Obs::new();
-    let password_ref = password.as_deref();
-
-    // OBS接続試行
-    match obs.connect(&host, port, password_ref).await {
-        Ok(_) => {
-            info!("Connected to OBS successfully");
-
-            // リプレイバッファ設定
-            if let Err(e) = obs.set_replay_buffer().await {
-                return Err(format!("Failed to set replay buffer: {}", e));
+    // すべてのOBSに接続する。確認に使った接続をそのままシステムで使う
+    let mut connected = Vec::new();
+    for target in &targets {
+        match obs_targets::connect(target).await {
+            Ok(obs) => connected.push((target.clone(), obs)),
+            Err(e) => {
+                error!("Failed to connect to OBS: {}", e);
+                return Err(e);
             }
+        }
+    }
 
-            // VLCソース初期化
-            if let Err(e) = obs.init_vlc_source().await {
-                return Err(format!("Failed to init VLC source: {}", e));
-            }
+    // 接続情報を保存
+    *state.obs_targets.lock().unwrap() = targets;
 
-            // 接続情報を保存
-            {
-                let mut conn_info = state.obs_connection_info.lock().unwrap();
-                *conn_info = Some((host.clone(), port, password.clone()));
-            }
+    // システム開始
+    let count = connected.len();
+    start_system(connected, state, app_handle).await?;
 
-            // システム開始
-            start_system(host, port, password, state, app_handle).await?;
+    Ok(format!("{}台のOBSに接続しました", count))
+}
 
-            Ok("OBS接続に成功しました".to_string())
-        }
-        Err(e) => {
-            error!("Failed to connect to OBS: {}", e);
-            Err(format!("OBS接続に失敗しました: {}", e))
-        }
-    }
+#[tauri::command]
+async fn get_obs_targets(state: tauri::State<'_, AppState>) -> Result<Vec<ObsTarget>, String> {
+    Ok(state.obs_targets.lock().unwrap().clone())
 }
 
 async fn start_system(
-    host: String,
-    port: u16,
-    password: Option<String>,
+    connected: Vec<(ObsTarget, obs::Obs)>,
     state: tauri::State<'_, AppState>,
     app_handle: tauri::AppHandle,
 ) -> Result<(), String> {
@@ -168,19 +1039,9 @@ async fn start_system(
     }
 
     // 別タスクでメインシステムを起動
-    let host_clone = host.clone();
-    let password_clone = password.clone();
-    let sleep_duration_clone = state.sleep_duration_sec.clone();
+    let state_clone = state.inner().clone();
     tokio::spawn(async move {
-        if let Err(e) = run_main_system(
-            host_clone,
-            port,
-            password_clone,
-            sleep_duration_clone,
-            app_handle,
-        )
-        .await
-        {
+        if let Err(e) = run_main_system(connected, state_clone, app_handle).await {
             error!("Main system error: {}", e);
         }
     });
@@ -189,64 +1050,154 @@ async fn start_system(
     Ok(())
 }
 
-async fn run_main_system(
-    host: String,
-    port: u16,
-    password: Option<String>,
-    sleep_duration: Arc<RwLock<u64>>,
-    app_handle: tauri::AppHandle,
+// connected: connect_obs_targetsで接続を確認したOBS
+async fn run_main_system<R: Runtime>(
+    connected: Vec<(ObsTarget, obs::Obs)>,
+    state: AppState,
+    app_handle: AppHandle<R>,
 ) -> Result<(), String> {
-    // OBS接続を再作成
-    let mut obs = obs::Obs::new();
-    let password_ref = password.as_deref();
-    obs.connect(&host, port, password_ref)
-        .await
-        .map_err(|e| format!("Failed to reconnect to OBS: {}", e))?;
-
-    obs.set_replay_buffer()
-        .await
-        .map_err(|e| format!("Failed to set replay buffer: {}", e))?;
-
-    obs.init_vlc_source()
-        .await
-        .map_err(|e| format!("Failed to init VLC source: {}", e))?;
-
     // VlcManager初期化
     let vlc_manager = VlcManager::new();
 
-    // イベントリスナー設定
+    // イベントリスナー設定。どの録画先で保存されたクリップも録画先の名前をアングルとして同じカタログに入れる
+    let source_record = state.source_record_settings.read().unwrap().clone();
+    let backends = obs_targets::backends(connected, &source_record)?;
     let (rb_tx, rb_rx) = mpsc::channel(32);
-    obs.set_event_listener(rb_tx)
-        .await
-        .map_err(|e| format!("Failed to set event listener: {}", e))?;
+    for target in &backends.capture {
+        let (tx, mut rx) = mpsc::channel(32);
+        target
+            .backend
+            .listen_saved_clips(tx)
+            .await
+            .map_err(|e| format!("Failed to set event listener on {}: {}", target.name, e))?;
+        let rb_tx = rb_tx.clone();
+        let angle = target.name.clone();
+        tokio::spawn(async move {
+            while let Some(path) = rx.recv().await {
+                let saved = SavedClip {
+                    angle: angle.clone(),
+                    path,
+                };
+                if rb_tx.send(saved).await.is_err() {
+                    break;
+                }
+            }
+        });
+    }
+
+    vlc_manager.set_event_listener(rb_rx, state.clone(), app_handle.clone());
+    *state.backends.lock().unwrap() = Some(backends.clone());
 
-    vlc_manager.set_event_listener(rb_rx, app_handle.clone());
+    spawn_disk_monitor(state.clone(), app_handle.clone());
+    scoreboard::spawn_scoreboard(
+        backends.overlay.clone(),
+        state.scoreboard_settings.clone(),
+        state.scoreboard_tx.subscribe(),
+    );
+    webhook::spawn_webhooks(
+        &state.api_events,
+        state.webhook_settings.clone(),
+        state.webhook_log.clone(),
+    );
 
-    // UDPサーバー開始
+    // 受信開始(MugiのUDPかStats API)
     let (tx, mut rx) = mpsc::channel::<String>(32);
-    tokio::spawn(async {
-        if let Err(e) = bind_socket(tx).await {
-            error!("UDP socket error: {}", e);
-        }
-    });
+    *state.mugi_tx.lock().unwrap() = Some(tx.clone());
+    let settings = state.event_source_settings.read().unwrap().clone();
+    let source = event_source::spawn_source(&settings, tx, &state);
+    *state.event_source.lock().unwrap() = Some(source);
+    let (trigger_tx, mut trigger_rx) = mpsc::channel::<RemoteTrigger>(32);
+    *state.trigger_tx.lock().unwrap() = Some(trigger_tx);
 
-    // UDPメッセージ処理 - 無限ループで動作し続ける
-    while let Some(d) = rx.recv().await {
+    // UDPメッセージと外部トリガーの処理 - 無限ループで動作し続ける
+    let mut last_ended_match: Option<String> = None;
+    loop {
+        let d = tokio::select! {
+            d = rx.recv() => match d {
+                Some(d) => d,
+                None => break,
+            },
+            Some(trigger) = trigger_rx.recv() => {
+                handle_trigger(trigger, &backends.capture, &state);
+                continue;
+            }
+        };
         let cmd = mugi_schema::parse_cmd(&d);
         match cmd {
-            Err(_) => error!("Failed to parse:{}", d),
+            Err(_) => {
+                error!("Failed to parse:{}", d);
+                state.mugi_diagnostics.lock().unwrap().record_parse_error();
+            }
             Ok(cmd) => {
-                if cmd == MugiCmd::Scored || cmd == MugiCmd::EpicSave {
+                let unknown_count = state.mugi_diagnostics.lock().unwrap().record(&cmd);
+                if let MugiCmd::Unknown { cmd: name, .. } = &cmd {
+                    // 毎フレーム届くコマンドもあるので初回だけ警告する
+                    if unknown_count == Some(1) {
+                        warn!("Unknown Mugi command: {}", name);
+                    }
+                    continue;
+                }
+                if cmd == MugiCmd::Init {
+                    let protocol = state.mugi_diagnostics.lock().unwrap().handshake(&d).clone();
+                    match &protocol {
+                        ProtocolStatus::Unsupported { version } => warn!(
+                            "Unsupported Mugi protocol version {} (supported: {}.x)",
+                            version,
+                            mugi_diagnostics::SUPPORTED_PROTOCOL_MAJOR
+                        ),
+                        _ => info!("Mugi initialized: {:?}", protocol),
+                    }
+                    if let Err(e) = app_handle.emit("mugi_handshake", &protocol) {
+                        error!("Failed to emit mugi_handshake event: {}", e);
+                    }
+                }
+                if let Err(e) = state.match_state.lock().unwrap().apply(&cmd, &d) {
+                    error!("Failed to apply {:?} to match state: {}", cmd, e);
+                }
+                update_stats(&cmd, &d, &state, &app_handle);
+                if cmd == MugiCmd::TeamNames {
+                    update_series(&state, &app_handle);
+                }
+                if let Some(kind) = clip_kind_for(&cmd) {
                     debug!("OBS fire!");
-                    let duration = {
-                        let sleep_dur = sleep_duration.read().unwrap();
-                        *sleep_dur
+                    spawn_save_replay(kind, None, backends.capture.clone(), state.clone());
+                }
+                if matches!(cmd, MugiCmd::End | MugiCmd::EndStats) {
+                    let (match_id, blue_goals, orange_goals) = {
+                        let match_state = state.match_state.lock().unwrap();
+                        let m = &*match_state;
+                        (m.match_id.clone(), m.blue_goals, m.orange_goals)
                     };
-                    tokio::time::sleep(std::time::Duration::from_secs(duration)).await;
-                    if let Err(e) = obs.save_replay_buffer().await {
-                        error!("Failed to save replay buffer: {}", e);
+                    if let Some(match_id) = &match_id {
+                        state.clip_catalog.lock().unwrap().record_result(
+                            match_id.clone(),
+                            blue_goals,
+                            orange_goals,
+                        );
+                    }
+                    // End/EndStatsの両方で二重に数えたり組み立てたりしない
+                    if let Some(match_id) = match_id
+                        && last_ended_match.as_ref() != Some(&match_id)
+                    {
+                        last_ended_match = Some(match_id.clone());
+                        let match_state = state.match_state.lock().unwrap().clone();
+                        api_server::publish(&state.api_events, "match_ended", &match_state);
+                        let series = state
+                            .series
+                            .lock()
+                            .unwrap()
+                            .record_result(&match_state)
+                            .cloned();
+                        if let Some(series) = series {
+                            if let Some(winner) = &series.winner {
+                                info!("Series {} won by {}", series.id, winner);
+                            }
+                            notify_series(&series, &state, &app_handle);
+                        }
+                        spawn_highlight_reel(match_id, state.clone(), app_handle.clone());
                     }
                 }
+                update_scoreboard(&state);
             }
         }
     }
@@ -255,6 +1206,250 @@ async fn run_main_system(
     Ok(())
 }
 
+// teamNamesの試合をシリーズに入れる。チームが変わったら新しいシリーズを始める
+fn update_series<R: Runtime>(state: &AppState, app_handle: &AppHandle<R>) {
+    let match_state = state.match_state.lock().unwrap().clone();
+    let (series, is_new) = {
+        let mut manager = state.series.lock().unwrap();
+        let previous_id = manager.current().map(|s| s.id);
+        match manager.on_team_names(&match_state) {
+            Some(series) => (series.clone(), Some(series.id) != previous_id),
+            None => return,
+        }
+    };
+    if is_new {
+        info!("Series {} started: {}", series.id, series.name);
+        begin_series(state);
+    }
+    notify_series(&series, state, app_handle);
+}
+
+// 新しいシリーズの成績を集計し直す
+// クリップの保存先はstate.seriesの現在のシリーズから決めるので、保存フォルダの設定は変えない
+fn begin_series(state: &AppState) {
+    state.player_stats.lock().unwrap().reset_series();
+}
+
+fn notify_series<R: Runtime>(series: &Series, state: &AppState, app_handle: &AppHandle<R>) {
+    update_scoreboard(state);
+    if let Err(e) = app_handle.emit("series_updated", series) {
+        error!("Failed to emit series_updated event: {}", e);
+    }
+}
+
+// 試合状況からスコアボードの値を作り、変わっていれば流す
+fn update_scoreboard(state: &AppState) {
+    let match_state = state.match_state.lock().unwrap().clone();
+    let values = ScoreboardValues::from_state(&match_state, state.series.lock().unwrap().current());
+    state.scoreboard_tx.send_if_modified(|current| {
+        if *current == values {
+            return false;
+        }
+        *current = values;
+        true
+    });
+}
+
+// 成績を集計し、変化があれば通知とファイルの書き出しをする
+fn update_stats<R: Runtime>(cmd: &MugiCmd, msg: &str, state: &AppState, app_handle: &AppHandle<R>) {
+    let snapshot = {
+        let match_state = state.match_state.lock().unwrap().clone();
+        let mut stats = state.player_stats.lock().unwrap();
+        match stats.apply(cmd, msg, &match_state) {
+            Ok(true) => stats.snapshot(),
+            Ok(false) => return,
+            Err(e) => {
+                error!("Failed to apply {:?} to stats: {}", cmd, e);
+                return;
+            }
+        }
+    };
+    if let Err(e) = app_handle.emit("stats_updated", &snapshot) {
+        error!("Failed to emit stats_updated event: {}", e);
+    }
+    let output = state.stats_output.read().unwrap().clone();
+    if output.enabled
+        && let Some(dir) = &output.dir
+        && let Err(e) = player_stats::write_files(dir, &snapshot)
+    {
+        error!("Failed to write stats files to {:?}: {}", dir, e);
+    }
+}
+
+// リプレイを保存するコマンドとクリップの種類
+fn clip_kind_for(cmd: &MugiCmd) -> Option<ClipKind> {
+    match cmd {
+        MugiCmd::Scored => Some(ClipKind::Goal),
+        MugiCmd::EpicSave => Some(ClipKind::EpicSave),
+        _ => None,
+    }
+}
+
+// 外部ツールからのトリガーを処理する
+fn handle_trigger(trigger: RemoteTrigger, capture: &[CaptureTarget], state: &AppState) {
+    match trigger {
+        RemoteTrigger::Clip(request) => {
+            debug!("Remote clip: {:?}", request.label);
+            spawn_save_replay(
+                ClipKind::Manual,
+                request.label,
+                capture.to_vec(),
+                state.clone(),
+            );
+        }
+    }
+}
+
+// 録画遅延後にすべての録画先のリプレイバッファを保存し、その時点の試合状況をカタログに積む
+// 遅延中もMugiのイベントを処理し続けるため別タスクで行う
+fn spawn_save_replay(
+    kind: ClipKind,
+    label: Option<String>,
+    capture: Vec<CaptureTarget>,
+    state: AppState,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let duration = {
+            let sleep_dur = state.sleep_duration_sec.read().unwrap();
+            *sleep_dur
+        };
+        // 手動保存はすぐに録画する
+        if kind != ClipKind::Manual {
+            tokio::time::sleep(std::time::Duration::from_secs(duration)).await;
+        }
+        let mut context = ClipContext::from_state(kind, &state.match_state.lock().unwrap());
+        context.label = label;
+        context.highlight_id = Some(state.clip_catalog.lock().unwrap().new_highlight_id());
+        // 録画先ごとに1つずつクリップが届くので、同じ状況をアングル名を変えて録画先の数だけ積む
+        let contexts: Vec<ClipContext> = capture
+            .iter()
+            .map(|target| ClipContext {
+                angle: target.name.clone(),
+                ..context.clone()
+            })
+            .collect();
+        {
+            let mut catalog = state.clip_catalog.lock().unwrap();
+            for context in &contexts {
+                catalog.push_pending(context.clone());
+            }
+        }
+        // Source Recordは書き終わるまで待つので、1つの録画先を待たずに全部の保存を頼む
+        let saves = capture.iter().map(|target| target.backend.save_replay());
+        let results = futures_util::future::join_all(saves).await;
+        for ((target, context), res) in capture.iter().zip(&contexts).zip(results) {
+            if let Err(e) = res {
+                error!("Failed to save replay buffer on {}: {}", target.name, e);
+                state.clip_catalog.lock().unwrap().cancel_pending(context);
+            }
+        }
+    })
+}
+
+// 保存フォルダの空き容量を定期的に確認し、不足したら警告(と保持ポリシーの適用)をする
+fn spawn_disk_monitor<R: Runtime>(state: AppState, app_handle: AppHandle<R>) {
+    tokio::spawn(async move {
+        let mut was_low = false;
+        loop {
+            let settings = state.retention_settings.read().unwrap().clone();
+            tokio::time::sleep(std::time::Duration::from_secs(
+                settings.check_interval_sec.max(1),
+            ))
+            .await;
+
+            let dir = {
+                let catalog = state.clip_catalog.lock().unwrap();
+                let storage = state.storage_settings.read().unwrap();
+                clip_retention::watch_dir(&catalog, &storage)
+            };
+            let Some(dir) = dir else {
+                continue;
+            };
+            let status = match clip_retention::disk_status(&dir, &settings) {
+                Ok(status) => status,
+                Err(e) => {
+                    error!("Failed to get free space of {:?}: {}", dir, e);
+                    continue;
+                }
+            };
+            if !status.is_low {
+                was_low = false;
+                continue;
+            }
+            if !was_low {
+                info!(
+                    "Low disk space: {} bytes free in {:?}",
+                    status.free_bytes, dir
+                );
+                if let Err(e) = app_handle.emit("disk_space_low", &status) {
+                    error!("Failed to emit disk_space_low event: {}", e);
+                }
+            }
+            was_low = true;
+
+            if settings.auto_apply {
+                let current_game = state.match_state.lock().unwrap().game_number;
+                let report =
+                    clip_retention::apply(&state.clip_catalog, current_game, &settings, false);
+                if report.clips.is_empty() {
+                    continue;
+                }
+                info!(
+                    "Retention removed {} clips ({} bytes)",
+                    report.clips.len(),
+                    report.freed_bytes
+                );
+                if let Err(e) = app_handle.emit("retention_applied", &report) {
+                    error!("Failed to emit retention_applied event: {}", e);
+                }
+            }
+        }
+    });
+}
+
+// 試合終了時にリールを組み立てて通知または再生する
+// 決勝ゴールの保存を待つため、録画遅延と保存待ちの後に組み立てる
+fn spawn_highlight_reel<R: Runtime>(match_id: String, state: AppState, app_handle: AppHandle<R>) {
+    let settings = state.reel_settings.read().unwrap().clone();
+    if settings.mode == ReelMode::Off {
+        return;
+    }
+    tokio::spawn(async move {
+        let duration = *state.sleep_duration_sec.read().unwrap();
+        tokio::time::sleep(std::time::Duration::from_secs(duration + 1)).await;
+        for _ in 0..50 {
+            if !state.clip_catalog.lock().unwrap().has_pending() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+        }
+
+        let reel = {
+            let catalog = state.clip_catalog.lock().unwrap();
+            highlight_reel::build_reel(&catalog, &match_id, &settings)
+        };
+        if reel.clips.is_empty() {
+            info!("No clips for highlight reel of {}", match_id);
+            return;
+        }
+        info!(
+            "Highlight reel for {}: {} clips ({:.1}s)",
+            match_id,
+            reel.clips.len(),
+            reel.total_duration_sec
+        );
+        if let Err(e) = app_handle.emit("highlight_reel_ready", &reel) {
+            error!("Failed to emit highlight_reel_ready event: {}", e);
+        }
+        if settings.mode != ReelMode::AutoPlay {
+            return;
+        }
+        if let Err(e) = play_paths(&state, reel.paths()).await {
+            error!("Failed to play highlight reel: {}", e);
+        }
+    });
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     console_subscriber::init();
@@ -281,9 +1476,67 @@ pub fn run() {
         .manage(AppState::new())
         .invoke_handler(tauri::generate_handler![
             connect_obs,
+            connect_obs_targets,
+            get_obs_targets,
             play_highlights,
             set_sleep_duration,
-            get_sleep_duration
+            get_sleep_duration,
+            get_reel_settings,
+            set_reel_settings,
+            build_highlight_reel,
+            top_highlights,
+            set_clip_trim,
+            get_export_settings,
+            set_export_settings,
+            export_highlights,
+            get_rename_settings,
+            set_rename_settings,
+            get_storage_settings,
+            set_storage_settings,
+            relink_clips,
+            get_retention_settings,
+            set_retention_settings,
+            get_disk_status,
+            apply_retention,
+            set_clip_favorite,
+            start_capture,
+            stop_capture,
+            replay_capture,
+            get_mugi_diagnostics,
+            get_stats,
+            reset_series_stats,
+            get_stats_output_settings,
+            set_stats_output_settings,
+            get_scoreboard_settings,
+            set_scoreboard_settings,
+            get_series,
+            list_series,
+            start_series,
+            end_series,
+            build_series_reel,
+            series_highlights,
+            list_clips,
+            list_saved_clips,
+            stop_playback,
+            save_clip_now,
+            get_status,
+            get_api_settings,
+            set_api_settings,
+            get_osc_settings,
+            set_osc_settings,
+            get_relay_settings,
+            set_relay_settings,
+            get_event_source_settings,
+            set_event_source_settings,
+            get_webhook_settings,
+            set_webhook_settings,
+            get_webhook_deliveries,
+            get_source_record_settings,
+            set_source_record_settings,
+            play_highlight_angles,
+            list_highlights,
+            get_angle_playback_settings,
+            set_angle_playback_settings
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -308,3 +1561,245 @@ async fn update(app: AppHandle) -> tauri_plugin_updater::Result<()> {
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use backend::ClipBackend;
+    use backend::fake::FakeBackend;
+    use mugi_schema::Goals;
+    use std::path::PathBuf;
+
+    fn test_state() -> AppState {
+        let state = AppState::new();
+        *state.sleep_duration_sec.write().unwrap() = 0;
+        *state.match_state.lock().unwrap() = MatchState {
+            match_id: Some("A".to_string()),
+            game_number: 1,
+            last_goal: Some(Goals {
+                team: "orange".to_string(),
+                score_id: "Player_2".to_string(),
+                assist_id: String::new(),
+            }),
+            ..MatchState::default()
+        };
+        state
+    }
+
+    fn capture(name: &str, backend: &Arc<FakeBackend>) -> Vec<CaptureTarget> {
+        vec![CaptureTarget {
+            name: name.to_string(),
+            backend: backend.clone(),
+        }]
+    }
+
+    #[test]
+    fn test_clip_kind_for() {
+        assert_eq!(clip_kind_for(&MugiCmd::Scored), Some(ClipKind::Goal));
+        assert_eq!(clip_kind_for(&MugiCmd::EpicSave), Some(ClipKind::EpicSave));
+        assert_eq!(clip_kind_for(&MugiCmd::Goals), None);
+    }
+
+    #[tokio::test]
+    async fn test_save_replay_attaches_context() {
+        let state = test_state();
+        let backend = Arc::new(FakeBackend::default());
+        let (tx, mut rx) = mpsc::channel(8);
+        backend.listen_saved_clips(tx).await.unwrap();
+
+        spawn_save_replay(
+            ClipKind::Goal,
+            None,
+            capture("main", &backend),
+            state.clone(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(backend.saved_clips().len(), 1);
+
+        let path = rx.recv().await.unwrap();
+        let clip = state.clip_catalog.lock().unwrap().attach(path);
+        assert_eq!(clip.context.kind, ClipKind::Goal);
+        assert_eq!(clip.context.match_id.as_deref(), Some("A"));
+        assert_eq!(clip.context.scorer.as_deref(), Some("Player_2"));
+    }
+
+    #[tokio::test]
+    async fn test_save_replay_keeps_label() {
+        let state = test_state();
+        let backend = Arc::new(FakeBackend::default());
+        let (tx, mut rx) = mpsc::channel(8);
+        backend.listen_saved_clips(tx).await.unwrap();
+
+        let label = Some("caster callout".to_string());
+        spawn_save_replay(
+            ClipKind::Manual,
+            label,
+            capture("main", &backend),
+            state.clone(),
+        )
+        .await
+        .unwrap();
+        let path = rx.recv().await.unwrap();
+        let clip = state.clip_catalog.lock().unwrap().attach(path);
+        assert_eq!(clip.context.kind, ClipKind::Manual);
+        assert_eq!(clip.context.scorer, None);
+        assert_eq!(clip.context.label.as_deref(), Some("caster callout"));
+    }
+
+    #[tokio::test]
+    async fn test_save_replay_failure_cancels_pending() {
+        let state = test_state();
+        let backend = Arc::new(FakeBackend {
+            fail_save: true,
+            ..FakeBackend::default()
+        });
+
+        spawn_save_replay(
+            ClipKind::EpicSave,
+            None,
+            capture("main", &backend),
+            state.clone(),
+        )
+        .await
+        .unwrap();
+        assert!(backend.saved_clips().is_empty());
+        assert!(!state.clip_catalog.lock().unwrap().has_pending());
+    }
+
+    #[tokio::test]
+    async fn test_save_replay_on_every_capture_target() {
+        let state = test_state();
+        let program = Arc::new(FakeBackend::default());
+        let iso = Arc::new(FakeBackend::default());
+        let offline = Arc::new(FakeBackend {
+            fail_save: true,
+            ..FakeBackend::default()
+        });
+        let (tx, mut rx) = mpsc::channel(8);
+        program.listen_saved_clips(tx.clone()).await.unwrap();
+        iso.listen_saved_clips(tx).await.unwrap();
+
+        let targets = [
+            capture("program", &program),
+            capture("offline", &offline),
+            capture("iso", &iso),
+        ]
+        .concat();
+        spawn_save_replay(ClipKind::Goal, None, targets, state.clone())
+            .await
+            .unwrap();
+        assert_eq!(program.saved_clips().len(), 1);
+        assert_eq!(iso.saved_clips().len(), 1);
+
+        // 保存できた2つの録画先のクリップに同じ試合状況が付く
+        for _ in 0..2 {
+            let path = rx.recv().await.unwrap();
+            let clip = state.clip_catalog.lock().unwrap().attach(path);
+            assert_eq!(clip.context.kind, ClipKind::Goal);
+            assert_eq!(clip.context.scorer.as_deref(), Some("Player_2"));
+        }
+        assert!(!state.clip_catalog.lock().unwrap().has_pending());
+    }
+
+    #[tokio::test]
+    async fn test_save_replay_tracks_angles() {
+        let state = test_state();
+        let program = Arc::new(FakeBackend::default());
+        let ball_cam = Arc::new(FakeBackend::default());
+        let targets = [capture("program", &program), capture("ball_cam", &ball_cam)].concat();
+        for _ in 0..2 {
+            spawn_save_replay(ClipKind::Goal, None, targets.clone(), state.clone())
+                .await
+                .unwrap();
+        }
+
+        // アングルごとに届く順番がずれても、同じトリガーのクリップは同じハイライトになる
+        let mut catalog = state.clip_catalog.lock().unwrap();
+        let ball_cam_clip = catalog.attach_angle("ball_cam", PathBuf::from("ball_cam.mkv"));
+        let program_clip = catalog.attach_angle("program", PathBuf::from("program.mkv"));
+        assert_eq!(ball_cam_clip.context.angle, "ball_cam");
+        assert_eq!(program_clip.context.angle, "program");
+        assert_eq!(program_clip.context.highlight_id, Some(0));
+        assert_eq!(ball_cam_clip.context.highlight_id, Some(0));
+
+        let next = catalog.attach_angle("program", PathBuf::from("program2.mkv"));
+        assert_eq!(next.context.highlight_id, Some(1));
+    }
+
+    #[tokio::test]
+    async fn test_save_replay_does_not_wait_for_slow_angles() {
+        let state = test_state();
+        let slow = Arc::new(FakeBackend {
+            save_delay: std::time::Duration::from_secs(1),
+            ..FakeBackend::default()
+        });
+        let fast = Arc::new(FakeBackend::default());
+        let targets = [capture("iso", &slow), capture("program", &fast)].concat();
+        let handle = spawn_save_replay(ClipKind::Goal, None, targets, state.clone());
+
+        // 先に並んだ録画先の書き出しを待たずに、次の録画先にも保存を頼む
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+        assert_eq!(fast.saved_clips().len(), 1);
+        assert!(slow.saved_clips().is_empty());
+        handle.await.unwrap();
+        assert_eq!(slow.saved_clips().len(), 1);
+    }
+
+    // Mugiのゴールから保存・カタログ登録・再生までを偽OBSで通す
+    #[tokio::test]
+    async fn test_goal_saves_and_plays_on_obs() {
+        let mock = obs_mock::MockObs::start().await;
+        let state = AppState::new();
+        *state.sleep_duration_sec.write().unwrap() = 0;
+        // 固定のUDPポートを使わないよう、つながらないStats APIを受信元にしてメッセージは直接流す
+        *state.event_source_settings.write().unwrap() = EventSourceSettings {
+            kind: event_source::EventSourceKind::StatsApi,
+            stats_api_addr: "127.0.0.1:1".to_string(),
+        };
+        let target = ObsTarget::single("127.0.0.1".to_string(), mock.port, None);
+        let obs = obs_targets::connect(&target).await.unwrap();
+        let app = tauri::test::mock_app();
+        tokio::spawn(run_main_system(
+            vec![(target, obs)],
+            state.clone(),
+            app.handle().clone(),
+        ));
+
+        let mut tx = None;
+        for _ in 0..100 {
+            tx = state.mugi_tx.lock().unwrap().clone();
+            if tx.is_some() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        let tx = tx.expect("system did not start");
+        tx.send(r#"{"cmd":"scored","data":null}"#.to_string())
+            .await
+            .unwrap();
+
+        let mut clip = None;
+        for _ in 0..100 {
+            clip = state.clip_catalog.lock().unwrap().clips().first().cloned();
+            if clip.is_some() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        let clip = clip.expect("clip was not saved");
+        assert_eq!(mock.requests_of("SaveReplayBuffer").len(), 1);
+        // 確認に使った接続をそのまま使い、接続し直さない
+        assert_eq!(mock.requests_of("GetReplayBufferStatus").len(), 1);
+        assert_eq!(clip.context.kind, ClipKind::Goal);
+        assert_eq!(clip.path, PathBuf::from("C:/replays/Replay 1.mkv"));
+
+        play_paths(&state, vec![clip.path.clone()]).await.unwrap();
+        let playlists = mock.requests_of("SetInputSettings");
+        assert_eq!(playlists.len(), 1);
+        assert_eq!(
+            playlists[0]["inputSettings"]["playlist"][0]["value"],
+            "C:/replays/Replay 1.mkv"
+        );
+    }
+}
@@ -0,0 +1,139 @@
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::mugi_schema::{self, _Stats, Goals, MatchId, MugiCmd, TeamNames, Time};
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum Side {
+    Blue,
+    Orange,
+}
+
+// チームを集計するときのキー。チーム名が無ければ色を使う
+pub fn team_key(name: &str, side: Side) -> String {
+    if !name.is_empty() {
+        return name.to_string();
+    }
+    match side {
+        Side::Blue => "blue".to_string(),
+        Side::Orange => "orange".to_string(),
+    }
+}
+
+// Mugiから届いたイベントを元に組み立てた現在の試合状況
+#[derive(Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct MatchState {
+    pub match_id: Option<String>,
+    // アプリ起動後に何試合目か(1始まり)
+    pub game_number: u32,
+    pub blue_name: String,
+    pub orange_name: String,
+    // 残り時間(秒)。オーバータイム中は経過時間
+    pub clock: Option<u32>,
+    pub is_overtime: bool,
+    pub blue_goals: u32,
+    pub orange_goals: u32,
+    pub last_goal: Option<Goals>,
+    pub is_ended: bool,
+    // 直前のゴール(キックオフ)以降のボールタッチ数とデモ数
+    pub play_touches: u32,
+    pub play_demos: u32,
+    // 直前のゴールに至るまでのボールタッチ数とデモ数
+    pub last_goal_touches: u32,
+    pub last_goal_demos: u32,
+    // statsで届いた全プレイヤーの累計ボールタッチ数
+    #[serde(skip)]
+    pub total_touches: u32,
+}
+
+impl MatchState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn apply(&mut self, cmd: &MugiCmd, msg: &str) -> Result<()> {
+        match cmd {
+            MugiCmd::MatchId => {
+                if let Some(data) = mugi_schema::parse_data::<MatchId>(msg)? {
+                    self.set_match_id(data.match_id);
+                }
+            }
+            MugiCmd::TeamNames => {
+                if let Some(data) = mugi_schema::parse_data::<TeamNames>(msg)? {
+                    self.set_match_id(data.match_id);
+                    self.blue_name = data.blue;
+                    self.orange_name = data.orange;
+                }
+            }
+            MugiCmd::Time => {
+                if let Some(data) = mugi_schema::parse_data::<Time>(msg)? {
+                    self.clock = Some(data.time);
+                    self.is_overtime = data.is_overtime != 0;
+                }
+            }
+            MugiCmd::Goals => {
+                if let Some(data) = mugi_schema::parse_data::<Goals>(msg)? {
+                    match self.side_of(&data.team) {
+                        Some(Side::Blue) => self.blue_goals += 1,
+                        Some(Side::Orange) => self.orange_goals += 1,
+                        None => {}
+                    }
+                    self.last_goal = Some(data);
+                    self.last_goal_touches = self.play_touches;
+                    self.last_goal_demos = self.play_demos;
+                    self.play_touches = 0;
+                    self.play_demos = 0;
+                }
+            }
+            MugiCmd::Stats => {
+                if let Some(stats) = mugi_schema::parse_data::<Vec<_Stats>>(msg)? {
+                    let total: u32 = stats.iter().map(|s| s.ball_touches).sum();
+                    self.play_touches += total.saturating_sub(self.total_touches);
+                    self.total_touches = total;
+                }
+            }
+            MugiCmd::Demolished => self.play_demos += 1,
+            MugiCmd::Start => self.is_ended = false,
+            MugiCmd::End => self.is_ended = true,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    // Mugiのteam表記("blue"/"0"やチーム名)をSideに変換
+    pub fn side_of(&self, team: &str) -> Option<Side> {
+        let lower = team.to_ascii_lowercase();
+        if lower == "blue" || lower == "0" || (!self.blue_name.is_empty() && team == self.blue_name)
+        {
+            Some(Side::Blue)
+        } else if lower == "orange"
+            || lower == "1"
+            || (!self.orange_name.is_empty() && team == self.orange_name)
+        {
+            Some(Side::Orange)
+        } else {
+            None
+        }
+    }
+
+    pub fn team_name(&self, side: Side) -> &str {
+        match side {
+            Side::Blue => &self.blue_name,
+            Side::Orange => &self.orange_name,
+        }
+    }
+
+    // 新しいmatchIdが来たら試合状況をリセット
+    fn set_match_id(&mut self, match_id: String) {
+        if self.match_id.as_deref() == Some(match_id.as_str()) {
+            return;
+        }
+        *self = Self {
+            match_id: Some(match_id),
+            game_number: self.game_number + 1,
+            ..Self::default()
+        };
+    }
+}
@@ -0,0 +1,148 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use tokio::process::Command;
+
+// ffprobeで取得した映像ストリームの情報
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct MediaInfo {
+    pub duration_sec: Option<f64>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub video_codec: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ProbeOutput {
+    #[serde(default)]
+    streams: Vec<ProbeStream>,
+    format: Option<ProbeFormat>,
+}
+
+#[derive(Deserialize)]
+struct ProbeStream {
+    codec_name: Option<String>,
+    width: Option<u32>,
+    height: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct ProbeFormat {
+    // ffprobeは数値を文字列で返す
+    duration: Option<String>,
+}
+
+// 外部ツールを実行してstdoutを返す
+pub async fn run_tool(program: &Path, args: &[String]) -> Result<Vec<u8>, String> {
+    let mut cmd = Command::new(program);
+    cmd.args(args);
+    // リリースビルドでツールのコンソールを表示しない
+    #[cfg(windows)]
+    cmd.creation_flags(0x08000000);
+    let output = cmd
+        .output()
+        .await
+        .map_err(|e| format!("Failed to run {:?}: {e}", program))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let tail: Vec<&str> = stderr.lines().rev().take(5).collect();
+        return Err(format!(
+            "{:?} exited with {}: {}",
+            program,
+            output.status,
+            tail.into_iter().rev().collect::<Vec<_>>().join(" / ")
+        ));
+    }
+    Ok(output.stdout)
+}
+
+fn parse_probe(stdout: &[u8]) -> Result<MediaInfo, String> {
+    let probe: ProbeOutput = serde_json::from_slice(stdout)
+        .map_err(|e| format!("Failed to parse ffprobe output: {e}"))?;
+    let stream = probe.streams.into_iter().next();
+    Ok(MediaInfo {
+        duration_sec: probe
+            .format
+            .and_then(|f| f.duration)
+            .and_then(|d| d.parse().ok()),
+        width: stream.as_ref().and_then(|s| s.width),
+        height: stream.as_ref().and_then(|s| s.height),
+        video_codec: stream.and_then(|s| s.codec_name),
+    })
+}
+
+pub async fn probe(ffprobe: &Path, path: &Path) -> Result<MediaInfo, String> {
+    let args: Vec<String> = vec![
+        "-v".into(),
+        "error".into(),
+        "-select_streams".into(),
+        "v:0".into(),
+        "-show_entries".into(),
+        "stream=codec_name,width,height:format=duration".into(),
+        "-of".into(),
+        "json".into(),
+        path.to_string_lossy().into_owned(),
+    ];
+    let stdout = run_tool(ffprobe, &args).await?;
+    parse_probe(&stdout)
+}
+
+// サムネイルのファイル名。クリップIDは起動ごとに0から振り直すので、動画のパスから決める
+// 起動をまたいで同じ名前になるようFNV-1aでハッシュする
+pub fn thumbnail_name(path: &Path) -> String {
+    let hash = path
+        .to_string_lossy()
+        .bytes()
+        .fold(0xcbf29ce484222325u64, |hash, b| {
+            (hash ^ b as u64).wrapping_mul(0x100000001b3)
+        });
+    format!("{:016x}.jpg", hash)
+}
+
+// at_sec地点のフレームを縮小してjpgで保存する
+pub async fn generate_thumbnail(
+    ffmpeg: &Path,
+    path: &Path,
+    at_sec: f64,
+    output: &Path,
+) -> Result<(), String> {
+    let args: Vec<String> = vec![
+        "-y".into(),
+        "-hide_banner".into(),
+        "-ss".into(),
+        at_sec.to_string(),
+        "-i".into(),
+        path.to_string_lossy().into_owned(),
+        "-frames:v".into(),
+        "1".into(),
+        "-vf".into(),
+        "scale=320:-2".into(),
+        output.to_string_lossy().into_owned(),
+    ];
+    run_tool(ffmpeg, &args).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_probe() {
+        let out = br#"{"programs":[],"streams":[{"codec_name":"h264","width":1920,"height":1080}],"format":{"duration":"5.016000"}}"#;
+        let info = parse_probe(out).unwrap();
+        assert_eq!(info.duration_sec, Some(5.016));
+        assert_eq!(info.width, Some(1920));
+        assert_eq!(info.height, Some(1080));
+        assert_eq!(info.video_codec.as_deref(), Some("h264"));
+    }
+
+    #[test]
+    fn test_thumbnail_name() {
+        let name = thumbnail_name(Path::new("C:/replays/Replay 1.mkv"));
+        assert_eq!(name, thumbnail_name(Path::new("C:/replays/Replay 1.mkv")));
+        assert_ne!(name, thumbnail_name(Path::new("C:/replays/Replay 2.mkv")));
+        assert_eq!(thumbnail_name(Path::new("")), "cbf29ce484222325.jpg");
+    }
+}
@@ -0,0 +1,119 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, LineWriter, Write};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::Sender;
+
+// キャプチャファイル1行分: 受信時刻(UNIX時間ms)と受信したdatagram
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CaptureRecord {
+    pub ts: u64,
+    pub data: String,
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+// 受信したdatagramをJSONLで書き出す
+pub struct CaptureWriter {
+    file: LineWriter<File>,
+}
+
+impl CaptureWriter {
+    pub fn create(path: &Path) -> Result<Self> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let file = File::create(path)?;
+        Ok(Self {
+            file: LineWriter::new(file),
+        })
+    }
+
+    pub fn write(&mut self, data: &str) -> Result<()> {
+        let record = CaptureRecord {
+            ts: now_ms(),
+            data: data.to_string(),
+        };
+        let line = serde_json::to_string(&record)?;
+        writeln!(self.file, "{}", line)?;
+        Ok(())
+    }
+}
+
+pub fn read_capture(path: &Path) -> Result<Vec<CaptureRecord>> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut records = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        records.push(serde_json::from_str(&line)?);
+    }
+    Ok(records)
+}
+
+// キャプチャの中身から受信したdatagramだけを取り出す(同梱のfixture用)
+#[cfg(test)]
+pub fn capture_messages(capture: &str) -> Vec<String> {
+    capture
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| serde_json::from_str::<CaptureRecord>(line).unwrap().data)
+        .collect()
+}
+
+// キャプチャを受信時と同じ間隔でtxに流す
+// speedは再生倍率。0以下なら待たずに流す
+pub async fn replay(records: &[CaptureRecord], speed: f64, tx: &Sender<String>) -> Result<()> {
+    let mut prev_ts = records.first().map(|r| r.ts);
+    for record in records {
+        if speed > 0.0
+            && let Some(prev) = prev_ts
+        {
+            let wait = record.ts.saturating_sub(prev) as f64 / speed;
+            tokio::time::sleep(Duration::from_millis(wait as u64)).await;
+        }
+        prev_ts = Some(record.ts);
+        tx.send(record.data.clone()).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tokio::sync::mpsc;
+
+    #[tokio::test]
+    async fn test_record_and_replay() {
+        let path = std::env::temp_dir().join(format!("mugi_capture_test_{}.jsonl", now_ms()));
+        let messages = [
+            r#"{"cmd":"matchId","data":{"matchId":"A"}}"#,
+            r#"{"cmd":"scored","data":null}"#,
+        ];
+        {
+            let mut writer = CaptureWriter::create(&path).unwrap();
+            for msg in messages {
+                writer.write(msg).unwrap();
+            }
+        }
+        let records = read_capture(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(records.len(), 2);
+
+        let (tx, mut rx) = mpsc::channel(8);
+        replay(&records, 0.0, &tx).await.unwrap();
+        for msg in messages {
+            assert_eq!(rx.recv().await.unwrap(), msg);
+        }
+    }
+}
@@ -0,0 +1,116 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::mugi_schema::{self, Init, MugiCmd};
+
+// 対応しているMugiのプロトコルのメジャーバージョン
+pub const SUPPORTED_PROTOCOL_MAJOR: u32 = 1;
+
+// initで受け取ったプロトコルの状態
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase", tag = "status")]
+pub enum ProtocolStatus {
+    // initをまだ受け取っていない
+    #[default]
+    Waiting,
+    // バージョンを送らない古いMugi
+    Legacy,
+    Supported {
+        version: String,
+    },
+    Unsupported {
+        version: String,
+    },
+}
+
+// Mugiの受信状況。診断用にフロントエンドへ返す
+#[derive(Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct MugiDiagnostics {
+    pub protocol: ProtocolStatus,
+    pub received: u64,
+    // JSONとして読めなかったメッセージの数
+    pub parse_errors: u64,
+    // 未対応のコマンドごとの受信数
+    pub unknown_cmds: BTreeMap<String, u64>,
+}
+
+impl MugiDiagnostics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // 受信したコマンドを数える。未対応のコマンドならその受信数を返す
+    pub fn record(&mut self, cmd: &MugiCmd) -> Option<u64> {
+        self.received += 1;
+        match cmd {
+            MugiCmd::Unknown { cmd, .. } => {
+                let count = self.unknown_cmds.entry(cmd.clone()).or_default();
+                *count += 1;
+                Some(*count)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn record_parse_error(&mut self) {
+        self.received += 1;
+        self.parse_errors += 1;
+    }
+
+    // initのバージョンを確認する。Mugiの再起動ごとに届くので毎回更新する
+    pub fn handshake(&mut self, msg: &str) -> &ProtocolStatus {
+        let init = mugi_schema::parse_data::<Init>(msg)
+            .ok()
+            .flatten()
+            .unwrap_or_default();
+        self.protocol = match init.version {
+            None => ProtocolStatus::Legacy,
+            Some(version) if is_supported(&version) => ProtocolStatus::Supported { version },
+            Some(version) => ProtocolStatus::Unsupported { version },
+        };
+        &self.protocol
+    }
+}
+
+fn is_supported(version: &str) -> bool {
+    version
+        .split('.')
+        .next()
+        .and_then(|major| major.trim().parse::<u32>().ok())
+        == Some(SUPPORTED_PROTOCOL_MAJOR)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_record_and_handshake() {
+        let mut diagnostics = MugiDiagnostics::new();
+        let unknown = mugi_schema::parse_cmd(r#"{"cmd":"replayWillEnd","data":null}"#).unwrap();
+        assert_eq!(diagnostics.record(&unknown), Some(1));
+        assert_eq!(diagnostics.record(&unknown), Some(2));
+        assert_eq!(diagnostics.record(&MugiCmd::Scored), None);
+        assert_eq!(diagnostics.unknown_cmds["replayWillEnd"], 2);
+        assert_eq!(diagnostics.received, 3);
+
+        assert_eq!(
+            *diagnostics.handshake(r#"{"cmd":"init","data":null}"#),
+            ProtocolStatus::Legacy
+        );
+        assert_eq!(
+            *diagnostics.handshake(r#"{"cmd":"init","data":{"version":"1.2"}}"#),
+            ProtocolStatus::Supported {
+                version: "1.2".to_string()
+            }
+        );
+        assert_eq!(
+            *diagnostics.handshake(r#"{"cmd":"init","data":{"version":"2.0"}}"#),
+            ProtocolStatus::Unsupported {
+                version: "2.0".to_string()
+            }
+        );
+    }
+}
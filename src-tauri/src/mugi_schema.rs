@@ -1,7 +1,7 @@
-use anyhow::{Result, anyhow};
-use serde::{Deserialize, Serialize};
+use anyhow::Result;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub enum MugiCmd {
     Init,
     EndReplay,
@@ -23,86 +23,98 @@ pub enum MugiCmd {
     SubScore,
     Score,
     Player,
+    // 未対応のコマンド。新しいMugiが追加したメッセージをそのまま保持する
+    Unknown {
+        cmd: String,
+        data: Option<serde_json::Value>,
+    },
+}
+
+// initで届くプロトコル情報。古いMugiはdataを送らない
+#[derive(Serialize, Deserialize, Debug, PartialEq, Default)]
+pub struct Init {
+    #[serde(default)]
+    pub version: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
-struct TeamNames {
-    blue: String,
-    orange: String,
+pub struct TeamNames {
+    pub blue: String,
+    pub orange: String,
     #[serde(rename = "matchId")]
-    match_id: String,
+    pub match_id: String,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct Demolished {
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Demolished {
     #[serde(rename = "receiverIndex")]
-    receiver_index: u32,
+    pub receiver_index: u32,
     #[serde(rename = "victimIndex")]
-    victim_index: u32,
+    pub victim_index: u32,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct MatchId {
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct MatchId {
     #[serde(rename = "matchId")]
-    match_id: String,
+    pub match_id: String,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct _Stats {
-    id: String,
-    teams: u32,
-    scores: u32,
-    goals: u32,
-    assists: u32,
-    saves: u32,
-    shots: u32,
-    demos: u32,
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct _Stats {
+    pub id: String,
+    pub teams: u32,
+    pub scores: u32,
+    pub goals: u32,
+    pub assists: u32,
+    pub saves: u32,
+    pub shots: u32,
+    pub demos: u32,
     #[serde(rename = "ballTouches")]
-    ball_touches: u32,
+    pub ball_touches: u32,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct Goals {
-    team: String,
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Goals {
+    pub team: String,
     #[serde(rename = "scoreId")]
-    score_id: String,
+    pub score_id: String,
     #[serde(rename = "assistId")]
-    assist_id: String,
+    pub assist_id: String,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct Time {
-    time: u32,
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Time {
+    pub time: u32,
     #[serde(rename = "isOvertime")]
-    is_overtime: u8,
+    pub is_overtime: u8,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct Boost {
-    boost: u32,
-    index: usize,
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Boost {
+    pub boost: u32,
+    pub index: usize,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct SubScore {
-    goals: u32,
-    shots: u32,
-    assists: u32,
-    saves: u32,
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct SubScore {
+    pub goals: u32,
+    pub shots: u32,
+    pub assists: u32,
+    pub saves: u32,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct Score {
-    score: u32,
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Score {
+    pub score: u32,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct Player {
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Player {
     #[serde(rename = "playerIndex")]
-    player_index: usize,
-    team: String,
+    pub player_index: usize,
+    pub team: String,
     #[serde(rename = "playerName")]
-    player_name: String,
+    pub player_name: String,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -114,8 +126,7 @@ struct MugiData<T> {
 
 pub fn parse_cmd(json: &str) -> Result<MugiCmd> {
     let data: MugiData<serde_json::Value> = serde_json::from_str(json)?;
-    let cmd = data.cmd.as_str();
-    let mugi_cmd = match cmd {
+    let mugi_cmd = match data.cmd.as_str() {
         "init" => MugiCmd::Init,
         "endReplay" => MugiCmd::EndReplay,
         "endStats" => MugiCmd::EndStats,
@@ -136,99 +147,207 @@ pub fn parse_cmd(json: &str) -> Result<MugiCmd> {
         "subScore" => MugiCmd::SubScore,
         "score" => MugiCmd::Score,
         "player" => MugiCmd::Player,
-        _ => return Err(anyhow!("mugi parse failed")),
+        _ => MugiCmd::Unknown {
+            cmd: data.cmd,
+            data: data.data,
+        },
     };
     Ok(mugi_cmd)
 }
 
+// cmdに対応するdataを型付きで取り出す
+pub fn parse_data<T: DeserializeOwned>(json: &str) -> Result<Option<T>> {
+    let data: MugiData<T> = serde_json::from_str(json)?;
+    Ok(data.data)
+}
+
 #[cfg(test)]
 mod test {
-
     use super::*;
+    use serde_json::Value;
+
+    const ALL_CMDS: [MugiCmd; 20] = [
+        MugiCmd::Init,
+        MugiCmd::EndReplay,
+        MugiCmd::EndStats,
+        MugiCmd::TeamNames,
+        MugiCmd::Demolished,
+        MugiCmd::Scored,
+        MugiCmd::MatchId,
+        MugiCmd::Start,
+        MugiCmd::End,
+        MugiCmd::Stats,
+        MugiCmd::Goals,
+        MugiCmd::EpicSave,
+        MugiCmd::Dbg,
+        MugiCmd::DisplayNames,
+        MugiCmd::PlayerTable,
+        MugiCmd::Time,
+        MugiCmd::Boost,
+        MugiCmd::SubScore,
+        MugiCmd::Score,
+        MugiCmd::Player,
+    ];
+
+    // dataを型付きで読み、書き戻したJSONが元と一致することを確認する
+    fn round_trip<T: DeserializeOwned + Serialize>(msg: &str) -> T {
+        let value: Value = serde_json::from_str(msg).unwrap();
+        let data: T = parse_data(msg).unwrap().expect("data is missing");
+        assert_eq!(serde_json::to_value(&data).unwrap(), value["data"], "{msg}");
+        data
+    }
 
-    // #[test]
-    // fn test_team_names(){
-    //     let msg = r#"{"cmd":"teamNames","data":{"blue":"","matchId":"DA3FB72C11F00213D67A6E8E78296A08","orange":""}}"#;
-    //     let parse = parse_cmd(msg).unwrap();
-    //     assert_eq!(parse,MugiCmd::TeamNames);
-    //     let msg:MugiData<TeamNames> = serde_json::from_str(msg).unwrap();
-    //     assert_eq!(msg.data,TeamNames{blue:"".to_owned(),orange:"".to_owned(),matchId:"DA3FB72C11F00213D67A6E8E78296A08".to_owned()});
-    // }
-    // #[test]
-    // fn test_display_names(){
-    //     let msg = r#"{"cmd":"displayNames","data":["Player_Bot_Tex","Player_Bot_Sabretooth","Player_Bot_Boomer","Player_Bot_Mountain","Player_Bot_Casper","Player_Bot_Bandit"]}"#;
-    //     let parse = parse_cmd(msg).unwrap();
-    //     assert_eq!(parse,MugiCmd::DisplayNames);
-    //     let msg:MugiData<DisplayNames> = serde_json::from_str(msg).unwrap();
-    //     let expect: DisplayNames = vec!["Player_Bot_Tex".to_string(),
-    //             "Player_Bot_Sabretooth".to_string(),
-    //             "Player_Bot_Boomer".to_string(),
-    //             "Player_Bot_Mountain".to_string(),
-    //             "Player_Bot_Casper".to_string(),
-    //             "Player_Bot_Bandit".to_string(),];
-    //     assert_eq!(msg.data,expect);
-    // }
+    fn check_payload(cmd: &MugiCmd, msg: &str) {
+        match cmd {
+            MugiCmd::Init => {
+                if let Some(init) = parse_data::<Init>(msg).unwrap() {
+                    round_trip::<Init>(msg);
+                    assert!(init.version.is_some());
+                }
+            }
+            MugiCmd::EndReplay
+            | MugiCmd::EndStats
+            | MugiCmd::Scored
+            | MugiCmd::Start
+            | MugiCmd::End
+            | MugiCmd::EpicSave => {
+                assert_eq!(parse_data::<Value>(msg).unwrap(), None);
+            }
+            MugiCmd::TeamNames => {
+                round_trip::<TeamNames>(msg);
+            }
+            MugiCmd::Demolished => {
+                round_trip::<Demolished>(msg);
+            }
+            MugiCmd::MatchId => {
+                round_trip::<MatchId>(msg);
+            }
+            MugiCmd::Stats => {
+                round_trip::<Vec<_Stats>>(msg);
+            }
+            MugiCmd::Goals => {
+                round_trip::<Goals>(msg);
+            }
+            MugiCmd::Dbg => {
+                round_trip::<String>(msg);
+            }
+            MugiCmd::DisplayNames | MugiCmd::PlayerTable => {
+                round_trip::<Vec<String>>(msg);
+            }
+            MugiCmd::Time => {
+                round_trip::<Time>(msg);
+            }
+            MugiCmd::Boost => {
+                round_trip::<Boost>(msg);
+            }
+            MugiCmd::SubScore => {
+                round_trip::<SubScore>(msg);
+            }
+            MugiCmd::Score => {
+                round_trip::<Score>(msg);
+            }
+            MugiCmd::Player => {
+                round_trip::<Player>(msg);
+            }
+            MugiCmd::Unknown { .. } => panic!("unknown command: {msg}"),
+        }
+    }
+
+    // session.jsonlは実機の記録ではなく手で作った合成データ(fixtures/mugi/README.md)
     #[test]
-    fn test_all() {
-        use std::fs::read_to_string;
-        let path = r#"F:\Github\Moca_rust\src-tauri\mugi_log.txt"#;
-        let lines: Vec<String> = read_to_string(path)
-            .unwrap()
-            .lines()
-            .map(String::from)
-            .collect();
-
-        for msg in lines {
-            println!("{}", msg);
+    fn test_session() {
+        let session = include_str!("../fixtures/mugi/session.jsonl");
+        let mut seen = Vec::new();
+        for msg in crate::mugi_capture::capture_messages(session) {
             let cmd = parse_cmd(&msg).unwrap();
-            match cmd {
-                MugiCmd::Init => {}
-                MugiCmd::EndReplay => {}
-                MugiCmd::EndStats => {}
-                MugiCmd::TeamNames => {
-                    serde_json::from_str::<MugiData<TeamNames>>(&msg).unwrap();
-                }
-                MugiCmd::Demolished => {
-                    serde_json::from_str::<MugiData<Demolished>>(&msg).unwrap();
-                }
-                MugiCmd::Scored => {}
-                MugiCmd::MatchId => {
-                    serde_json::from_str::<MugiData<MatchId>>(&msg).unwrap();
-                }
-                MugiCmd::Start => {}
-                MugiCmd::End => {}
-                MugiCmd::Stats => {
-                    serde_json::from_str::<MugiData<Vec<_Stats>>>(&msg).unwrap();
-                }
-                MugiCmd::Goals => {
-                    serde_json::from_str::<MugiData<Goals>>(&msg).unwrap();
-                }
-                MugiCmd::Dbg => {
-                    serde_json::from_str::<MugiData<String>>(&msg).unwrap();
-                }
-                MugiCmd::DisplayNames => {
-                    serde_json::from_str::<MugiData<Vec<String>>>(&msg).unwrap();
-                }
-                MugiCmd::PlayerTable => {
-                    serde_json::from_str::<MugiData<Vec<String>>>(&msg).unwrap();
-                }
-                MugiCmd::Time => {
-                    serde_json::from_str::<MugiData<Time>>(&msg).unwrap();
-                }
-                MugiCmd::Boost => {
-                    serde_json::from_str::<MugiData<Boost>>(&msg).unwrap();
-                }
-                MugiCmd::SubScore => {
-                    serde_json::from_str::<MugiData<SubScore>>(&msg).unwrap();
-                }
-                MugiCmd::Score => {
-                    serde_json::from_str::<MugiData<Score>>(&msg).unwrap();
-                }
-                MugiCmd::Player => {
-                    serde_json::from_str::<MugiData<Player>>(&msg).unwrap();
-                }
-                _ => panic!(),
+            check_payload(&cmd, &msg);
+            if !seen.contains(&cmd) {
+                seen.push(cmd);
             }
         }
+        for cmd in ALL_CMDS {
+            assert!(seen.contains(&cmd), "{:?} is not in session.jsonl", cmd);
+        }
+    }
+
+    #[test]
+    fn test_team_names() {
+        let msg = include_str!("../fixtures/mugi/empty_team_names.json").trim();
+        assert_eq!(parse_cmd(msg).unwrap(), MugiCmd::TeamNames);
+        let names = round_trip::<TeamNames>(msg);
+        assert_eq!(
+            names,
+            TeamNames {
+                blue: String::new(),
+                orange: String::new(),
+                match_id: "DA3FB72C11F00213D67A6E8E78296A08".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_display_names() {
+        let msg = include_str!("../fixtures/mugi/bot_display_names.json").trim();
+        assert_eq!(parse_cmd(msg).unwrap(), MugiCmd::DisplayNames);
+        let names = round_trip::<Vec<String>>(msg);
+        assert_eq!(names.len(), 6);
+        assert!(names.iter().all(|n| n.starts_with("Player_Bot_")));
+
+        // ボットのゴールはアシスト無しで届く
+        let msg = include_str!("../fixtures/mugi/bot_goal.json").trim();
+        let goal = round_trip::<Goals>(msg);
+        assert_eq!(goal.score_id, "Player_Bot_Tex");
+        assert!(goal.assist_id.is_empty());
+    }
+
+    #[test]
+    fn test_overtime() {
+        let msg = include_str!("../fixtures/mugi/overtime.json").trim();
+        assert_eq!(parse_cmd(msg).unwrap(), MugiCmd::Time);
+        let time = round_trip::<Time>(msg);
+        assert_eq!(time.time, 42);
+        assert_eq!(time.is_overtime, 1);
+    }
+
+    #[test]
+    fn test_unknown_cmd() {
+        let msg = include_str!("../fixtures/mugi/unknown_cmd.json").trim();
+        assert_eq!(
+            parse_cmd(msg).unwrap(),
+            MugiCmd::Unknown {
+                cmd: "replayWillEnd".to_string(),
+                data: None,
+            }
+        );
+        // dataもそのまま保持する
+        let msg = include_str!("../fixtures/mugi/unknown_cmd_with_data.json").trim();
+        assert_eq!(
+            parse_cmd(msg).unwrap(),
+            MugiCmd::Unknown {
+                cmd: "replayWillEnd".to_string(),
+                data: Some(serde_json::json!({"frames": 120})),
+            }
+        );
+        assert!(parse_cmd("not json").is_err());
+    }
+
+    #[test]
+    fn test_init() {
+        let msg = include_str!("../fixtures/mugi/init_with_version.json").trim();
+        assert_eq!(parse_cmd(msg).unwrap(), MugiCmd::Init);
+        let init = round_trip::<Init>(msg);
+        assert_eq!(init.version.as_deref(), Some("1.2"));
+    }
+
+    #[test]
+    fn test_missing_data() {
+        let msg = include_str!("../fixtures/mugi/missing_data.json").trim();
+        assert_eq!(parse_cmd(msg).unwrap(), MugiCmd::Scored);
+        assert_eq!(parse_data::<Value>(msg).unwrap(), None);
+
+        // dataが必要なコマンドでも欠けていればNoneになる
+        let msg = include_str!("../fixtures/mugi/goals_missing_data.json").trim();
+        assert_eq!(parse_cmd(msg).unwrap(), MugiCmd::Goals);
+        assert_eq!(parse_data::<Goals>(msg).unwrap(), None);
     }
 }
@@ -1,27 +1,53 @@
 use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
 
 use futures_util::{StreamExt, pin_mut};
-use log::debug;
-use obws::{Client, events::Event, requests::custom::source_settings::SlideshowFile};
-use tokio::sync::{OnceCell, mpsc::Sender};
+use log::{debug, error};
+use obws::{
+    Client, common::MediaAction, events::Event, requests::custom::source_settings::SlideshowFile,
+    requests::inputs::InputId, responses::media_inputs::MediaState,
+};
+use tokio::sync::{OnceCell, mpsc::Sender, watch};
+
+use crate::clip_storage;
 
 use time::Duration;
 const UNIQUE_REPLAY_SOURCE_NAME: &str = "RL_REPLAY_VLC_SOURCE";
+// メディアの状態を確認する間隔
+const MEDIA_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(100);
+// クリップの残り時間を過ぎてもout点・終端に達しないときに待つ時間
+const PLAYBACK_GRACE: std::time::Duration = std::time::Duration::from_secs(5);
+// 長さが分からないクリップを待つ上限(秒)
+const MAX_CLIP_SEC: f64 = 600.0;
+
+// 再生するクリップとin/out点(秒)
+#[derive(Clone)]
+pub struct PlaybackItem {
+    pub path: PathBuf,
+    pub in_sec: Option<f64>,
+    pub out_sec: Option<f64>,
+}
 
 pub struct Obs {
-    client: Option<Client>,
+    client: Option<Arc<Client>>,
+    // 切り替え中のトリム付きプレイリストを止めるためのトークン
+    playlist: Mutex<Option<watch::Sender<bool>>>,
     host: OnceCell<String>,
     port: OnceCell<u16>,
     password: OnceCell<Option<String>>,
+    // 進まないクリップを諦めるまでの猶予。テストでは短くする
+    playback_grace: std::time::Duration,
 }
 
 impl Obs {
     pub fn new() -> Self {
         Obs {
             client: None,
+            playlist: Mutex::new(None),
             host: OnceCell::new(),
             port: OnceCell::new(),
             password: OnceCell::new(),
+            playback_grace: PLAYBACK_GRACE,
         }
     }
     pub async fn connect(
@@ -31,7 +57,7 @@ impl Obs {
         password: Option<&str>,
     ) -> Result<(), obws::error::Error> {
         let client = Client::connect(host, port, password).await?;
-        self.client = Some(client);
+        self.client = Some(Arc::new(client));
         self.host.set(host.to_string()).unwrap();
         self.port.set(port).unwrap();
         if let Some(pass) = password {
@@ -42,7 +68,7 @@ impl Obs {
         Ok(())
     }
 
-    fn get_client(&self) -> Result<&Client, String> {
+    fn get_client(&self) -> Result<&Arc<Client>, String> {
         let client = &self.client;
         let client = match client {
             Some(c) => c,
@@ -72,6 +98,18 @@ impl Obs {
         Ok(())
     }
 
+    // 録画フォルダにある動画。リプレイバッファも録画フォルダに保存される
+    pub async fn list_replays(&self) -> Result<Vec<PathBuf>, String> {
+        let client = self.get_client()?;
+        let dir = client
+            .config()
+            .record_directory()
+            .await
+            .map_err(|e| e.to_string())?;
+        let videos = clip_storage::list_videos(std::path::Path::new(&dir));
+        Ok(videos.into_iter().map(|(path, _)| path).collect())
+    }
+
     pub async fn save_replay_buffer(&self) -> Result<(), String> {
         let client = self.get_client()?;
         let res = client.replay_buffer().save().await;
@@ -87,7 +125,7 @@ impl Obs {
         }
 
         let client = self.get_client()?;
-        let current_scene = self.get_current_scene().await?;
+        let current_scene = get_current_scene(client).await?;
 
         let vlc_setting = obws::requests::custom::source_settings::VlcSource {
             loop_: false,
@@ -115,76 +153,86 @@ impl Obs {
         Ok(())
     }
 
-    pub async fn play_vlc_source(&self, movie_pathes: &[PathBuf]) -> Result<(), String> {
-        let client = self.get_client()?;
-        let playlists: Vec<SlideshowFile> = movie_pathes
+    // in/out点付きでクリップを順番に再生する
+    // トリムが無ければplaylistにまとめて渡す。あれば最初のクリップを再生したら返し、
+    // 残りの切り替えは別タスクで行う
+    pub async fn play_clips(&self, items: &[PlaybackItem]) -> Result<(), String> {
+        self.cancel_playlist();
+        let client = self.get_client()?.clone();
+        if items
             .iter()
-            .map(|path| SlideshowFile {
-                value: path.as_path(),
-                hidden: false,
-                selected: false,
-            })
-            .collect();
-        let vlc_setting = obws::requests::custom::source_settings::VlcSource {
-            loop_: false,
-            shuffle: false,
-            playback_behavior:
-                obws::requests::custom::source_settings::PlaybackBehavior::StopRestart,
-            playlist: &playlists,
-            network_caching: Duration::milliseconds(100),
-            track: 1,
-            subtitle_enable: false,
-            subtitle: 0,
-        };
+            .all(|item| item.in_sec.is_none() && item.out_sec.is_none())
+        {
+            let movie_pathes: Vec<PathBuf> = items.iter().map(|item| item.path.clone()).collect();
+            return play_vlc_source(&client, &movie_pathes).await;
+        }
+
+        // 開始を待つ間に止められても、後から切り替えが始まらないよう先にトークンを置く
+        let (cancel, mut cancelled) = watch::channel(false);
+        *self.playlist.lock().unwrap() = Some(cancel);
+        start_item(&client, &items[0]).await?;
+        let items = items.to_vec();
+        let grace = self.playback_grace;
+        tokio::spawn(async move {
+            tokio::select! {
+                res = run_playlist(&client, &items, grace) => {
+                    if let Err(e) = res {
+                        error!("Playlist stopped: {e}");
+                    }
+                }
+                _ = cancelled.changed() => debug!("Playlist cancelled"),
+            }
+        });
+        Ok(())
+    }
+
+    // 切り替え中のプレイリストを止める
+    fn cancel_playlist(&self) {
+        if let Some(cancel) = self.playlist.lock().unwrap().take() {
+            let _ = cancel.send(true);
+        }
+    }
+
+    pub async fn stop_media(&self) -> Result<(), String> {
+        self.cancel_playlist();
+        stop_media(self.get_client()?).await
+    }
+
+    // テキスト/ブラウザソースなど任意の入力の設定を上書きせずに更新する
+    pub async fn set_input_settings(
+        &self,
+        input: &str,
+        settings: &serde_json::Value,
+    ) -> Result<(), String> {
+        let client = self.get_client()?;
         let input_setting = obws::requests::inputs::SetSettings {
-            input: obws::requests::inputs::InputId::Name(UNIQUE_REPLAY_SOURCE_NAME),
+            input: InputId::Name(input),
             overlay: Some(true),
-            settings: &vlc_setting,
+            settings,
         };
         let res = client.inputs().set_settings(input_setting).await;
-        match res {
-            Ok(_) => debug!("VLC source updated"),
-            Err(e) => return Err(format!("Failed to create VLC source: {e}")),
-        }
-        // Sourceの有効化
-        let current_scene = self.get_current_scene().await?;
-        let current_scene_id = current_scene.id;
-        let scene_items = client
-            .scene_items()
-            .list(current_scene_id.clone().into())
-            .await;
-        let scene_items = match scene_items {
-            Ok(scene_items) => scene_items,
-            Err(_) => return Err("Failed to get scene items".to_string()),
-        };
-        let unique_replay_source_item = match scene_items
-            .iter()
-            .find(|&item| item.source_name == UNIQUE_REPLAY_SOURCE_NAME)
-        {
-            Some(d) => d,
-            None => return Err("Failed to find unique_replay_source_item".to_string()),
-        };
-        let set_enabled: obws::requests::scene_items::SetEnabled<'_> =
-            obws::requests::scene_items::SetEnabled {
-                scene: current_scene_id.into(),
-                item_id: unique_replay_source_item.id,
-                enabled: true,
-            };
-        let res = client.scene_items().set_enabled(set_enabled).await;
         if let Err(e) = res {
-            return Err(format!("Failed to set VLC source enabled: {e}"));
+            return Err(format!("Failed to update {input}: {e}"));
         }
         Ok(())
     }
 
-    async fn get_current_scene(
+    // プラグインが登録したベンダーリクエスト(Source Recordなど)を呼ぶ
+    pub async fn call_vendor_request(
         &self,
-    ) -> Result<obws::responses::scenes::CurrentProgramScene, String> {
+        vendor: &str,
+        request_type: &str,
+        data: &serde_json::Value,
+    ) -> Result<serde_json::Value, String> {
         let client = self.get_client()?;
-        let current_scene = client.scenes().current_program_scene().await;
-        match current_scene {
-            Ok(current_scene) => Ok(current_scene),
-            Err(_) => Err("Failed to get current scene".to_string()),
+        let request = obws::requests::general::CallVendorRequest {
+            vendor_name: vendor,
+            request_type,
+            request_data: data,
+        };
+        match client.general().call_vendor_request(request).await {
+            Ok(res) => Ok(res.response_data),
+            Err(e) => Err(format!("Failed to call {vendor} {request_type}: {e}")),
         }
     }
 
@@ -208,17 +256,31 @@ impl Obs {
     }
 
     pub async fn set_event_listener(&self, tx: Sender<PathBuf>) -> Result<(), String> {
-        let host = self.host.get().unwrap();
-        let port = self.port.get().unwrap().to_owned();
-        let password = self.password.get().unwrap().as_ref().map(|d| d.as_str());
+        let (Some(host), Some(port), Some(password)) =
+            (self.host.get(), self.port.get(), self.password.get())
+        else {
+            return Err("OBSに接続していません".to_string());
+        };
+        let password = password.as_deref();
 
-        let client = Client::connect(host, port, password).await.unwrap();
+        let client = Client::connect(host, *port, password)
+            .await
+            .map_err(|e| format!("Failed to connect for events: {e}"))?;
         tokio::spawn(async move {
-            let events = client.events().unwrap();
+            let events = match client.events() {
+                Ok(events) => events,
+                Err(e) => {
+                    error!("Failed to subscribe to OBS events: {}", e);
+                    return;
+                }
+            };
             pin_mut!(events);
             while let Some(event) = events.next().await {
-                if let Event::ReplayBufferSaved { path } = event {
-                    tx.send(path).await.unwrap();
+                if let Event::ReplayBufferSaved { path } = event
+                    && tx.send(path).await.is_err()
+                {
+                    error!("Saved clip listener closed");
+                    return;
                 }
             }
         });
@@ -226,3 +288,408 @@ impl Obs {
         Ok(())
     }
 }
+
+async fn play_vlc_source(client: &Client, movie_pathes: &[PathBuf]) -> Result<(), String> {
+    let playlists: Vec<SlideshowFile> = movie_pathes
+        .iter()
+        .map(|path| SlideshowFile {
+            value: path.as_path(),
+            hidden: false,
+            selected: false,
+        })
+        .collect();
+    let vlc_setting = obws::requests::custom::source_settings::VlcSource {
+        loop_: false,
+        shuffle: false,
+        playback_behavior: obws::requests::custom::source_settings::PlaybackBehavior::StopRestart,
+        playlist: &playlists,
+        network_caching: Duration::milliseconds(100),
+        track: 1,
+        subtitle_enable: false,
+        subtitle: 0,
+    };
+    let input_setting = obws::requests::inputs::SetSettings {
+        input: obws::requests::inputs::InputId::Name(UNIQUE_REPLAY_SOURCE_NAME),
+        overlay: Some(true),
+        settings: &vlc_setting,
+    };
+    let res = client.inputs().set_settings(input_setting).await;
+    match res {
+        Ok(_) => debug!("VLC source updated"),
+        Err(e) => return Err(format!("Failed to create VLC source: {e}")),
+    }
+    // Sourceの有効化
+    let current_scene = get_current_scene(client).await?;
+    let current_scene_id = current_scene.id;
+    let scene_items = client
+        .scene_items()
+        .list(current_scene_id.clone().into())
+        .await;
+    let scene_items = match scene_items {
+        Ok(scene_items) => scene_items,
+        Err(_) => return Err("Failed to get scene items".to_string()),
+    };
+    let unique_replay_source_item = match scene_items
+        .iter()
+        .find(|&item| item.source_name == UNIQUE_REPLAY_SOURCE_NAME)
+    {
+        Some(d) => d,
+        None => return Err("Failed to find unique_replay_source_item".to_string()),
+    };
+    let set_enabled: obws::requests::scene_items::SetEnabled<'_> =
+        obws::requests::scene_items::SetEnabled {
+            scene: current_scene_id.into(),
+            item_id: unique_replay_source_item.id,
+            enabled: true,
+        };
+    let res = client.scene_items().set_enabled(set_enabled).await;
+    if let Err(e) = res {
+        return Err(format!("Failed to set VLC source enabled: {e}"));
+    }
+    Ok(())
+}
+
+async fn get_current_scene(
+    client: &Client,
+) -> Result<obws::responses::scenes::CurrentProgramScene, String> {
+    let current_scene = client.scenes().current_program_scene().await;
+    match current_scene {
+        Ok(current_scene) => Ok(current_scene),
+        Err(_) => Err("Failed to get current scene".to_string()),
+    }
+}
+
+// 前のクリップを止めてから切り替えて、in点にシークする
+// 止めずに切り替えると前のクリップのPlayingが返り、開始を待てない
+async fn start_item(client: &Client, item: &PlaybackItem) -> Result<(), String> {
+    stop_media(client).await?;
+    play_vlc_source(client, std::slice::from_ref(&item.path)).await?;
+    wait_media_playing(client).await?;
+    if let Some(in_sec) = item.in_sec {
+        set_media_cursor(client, in_sec).await?;
+    }
+    Ok(())
+}
+
+// 最初のクリップはplay_clipsで開始済み
+async fn run_playlist(
+    client: &Client,
+    items: &[PlaybackItem],
+    grace: std::time::Duration,
+) -> Result<(), String> {
+    for (index, item) in items.iter().enumerate() {
+        if index > 0 {
+            start_item(client, item).await?;
+        }
+        wait_media_until(client, item, grace).await?;
+    }
+    if items.last().is_some_and(|item| item.out_sec.is_some()) {
+        stop_media(client).await?;
+    }
+    Ok(())
+}
+
+async fn get_media_status(
+    client: &Client,
+) -> Result<obws::responses::media_inputs::MediaStatus, String> {
+    let res = client
+        .media_inputs()
+        .status(InputId::Name(UNIQUE_REPLAY_SOURCE_NAME))
+        .await;
+    match res {
+        Ok(status) => Ok(status),
+        Err(e) => Err(format!("Failed to get media status: {e}")),
+    }
+}
+
+async fn set_media_cursor(client: &Client, sec: f64) -> Result<(), String> {
+    let res = client
+        .media_inputs()
+        .set_cursor(
+            InputId::Name(UNIQUE_REPLAY_SOURCE_NAME),
+            Duration::seconds_f64(sec),
+        )
+        .await;
+    if let Err(e) = res {
+        return Err(format!("Failed to seek VLC source: {e}"));
+    }
+    Ok(())
+}
+
+async fn stop_media(client: &Client) -> Result<(), String> {
+    let res = client
+        .media_inputs()
+        .trigger_action(InputId::Name(UNIQUE_REPLAY_SOURCE_NAME), MediaAction::Stop)
+        .await;
+    if let Err(e) = res {
+        return Err(format!("Failed to stop VLC source: {e}"));
+    }
+    Ok(())
+}
+
+async fn wait_media_playing(client: &Client) -> Result<(), String> {
+    // 開くまで最大3秒待つ
+    for _ in 0..30 {
+        if get_media_status(client).await?.state == MediaState::Playing {
+            return Ok(());
+        }
+        tokio::time::sleep(MEDIA_POLL_INTERVAL).await;
+    }
+    Err("Timed out waiting for VLC source".to_string())
+}
+
+// out点に達するか、再生が終わるまで待つ
+// 一時停止されたままなどで進まないときは、残りの長さに猶予を足した時間で諦める
+async fn wait_media_until(
+    client: &Client,
+    item: &PlaybackItem,
+    grace: std::time::Duration,
+) -> Result<(), String> {
+    let mut deadline = None;
+    loop {
+        let status = get_media_status(client).await?;
+        match status.state {
+            MediaState::Playing
+            | MediaState::Opening
+            | MediaState::Buffering
+            | MediaState::Paused => {}
+            _ => return Ok(()),
+        }
+        let cursor = status.cursor.map(|c| c.as_seconds_f64());
+        if let (Some(out_sec), Some(cursor)) = (item.out_sec, cursor)
+            && cursor >= out_sec
+        {
+            return Ok(());
+        }
+        let deadline = *deadline.get_or_insert_with(|| {
+            let end = item.out_sec.or(status.duration.map(|d| d.as_seconds_f64()));
+            let remaining = match end {
+                Some(end) => end - cursor.or(item.in_sec).unwrap_or(0.0),
+                None => MAX_CLIP_SEC,
+            };
+            tokio::time::Instant::now()
+                + std::time::Duration::from_secs_f64(remaining.max(0.0))
+                + grace
+        });
+        if tokio::time::Instant::now() >= deadline {
+            return Err("Timed out waiting for the clip to finish".to_string());
+        }
+        tokio::time::sleep(MEDIA_POLL_INTERVAL).await;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::obs_mock::MockObs;
+    use tokio::sync::mpsc;
+
+    async fn connect(mock: &MockObs) -> Obs {
+        let mut obs = Obs::new();
+        obs.connect("127.0.0.1", mock.port, None).await.unwrap();
+        obs
+    }
+
+    #[tokio::test]
+    async fn test_list_replays() {
+        let dir = std::env::temp_dir().join(format!("obs_replays_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("Replay 1.mkv"), b"clip").unwrap();
+        std::fs::write(dir.join("notes.txt"), b"not a video").unwrap();
+        let mock = MockObs::start().await;
+        mock.state.lock().unwrap().record_directory = dir.to_string_lossy().into_owned();
+        let obs = connect(&mock).await;
+
+        let replays = obs.list_replays().await.unwrap();
+        assert_eq!(replays, vec![dir.join("Replay 1.mkv")]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    fn item(path: &str, in_sec: Option<f64>, out_sec: Option<f64>) -> PlaybackItem {
+        PlaybackItem {
+            path: PathBuf::from(path),
+            in_sec,
+            out_sec,
+        }
+    }
+
+    // VLCソースに設定されたプレイリストの先頭のクリップを順番に返す
+    fn played(mock: &MockObs) -> Vec<String> {
+        mock.requests_of("SetInputSettings")
+            .iter()
+            .map(|d| {
+                d["inputSettings"]["playlist"][0]["value"]
+                    .as_str()
+                    .unwrap()
+                    .to_string()
+            })
+            .collect()
+    }
+
+    fn last_media_action(mock: &MockObs) -> Option<String> {
+        let actions = mock.requests_of("TriggerMediaInputAction");
+        Some(actions.last()?["mediaAction"].as_str()?.to_string())
+    }
+
+    async fn wait_for(cond: impl Fn() -> bool) {
+        for _ in 0..500 {
+            if cond() {
+                return;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        panic!("condition not met");
+    }
+
+    #[tokio::test]
+    async fn test_save_replay_buffer() {
+        let mock = MockObs::start().await;
+        let obs = connect(&mock).await;
+
+        // 停止中なら開始する
+        obs.set_replay_buffer().await.unwrap();
+        obs.set_replay_buffer().await.unwrap();
+        let starts = mock.requests_of("StartReplayBuffer");
+        assert_eq!(starts.len(), 1);
+
+        let (tx, mut rx) = mpsc::channel(8);
+        obs.set_event_listener(tx).await.unwrap();
+        obs.save_replay_buffer().await.unwrap();
+        let path = tokio::time::timeout(std::time::Duration::from_secs(5), rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(path, PathBuf::from("C:/replays/Replay 1.mkv"));
+    }
+
+    #[tokio::test]
+    async fn test_call_vendor_request() {
+        let mock = MockObs::start().await;
+        let obs = connect(&mock).await;
+
+        let data = serde_json::json!({"source": "Ball Cam"});
+        // フィルタのリプレイバッファが止まっていれば保存できない
+        assert!(
+            obs.call_vendor_request("source-record", "replay_buffer_save", &data)
+                .await
+                .is_err()
+        );
+        obs.call_vendor_request("source-record", "replay_buffer_start", &data)
+            .await
+            .unwrap();
+        obs.call_vendor_request("source-record", "replay_buffer_save", &data)
+            .await
+            .unwrap();
+        let requests = mock.requests_of("CallVendorRequest");
+        assert_eq!(requests[2]["requestType"], "replay_buffer_save");
+        assert_eq!(requests[2]["requestData"], data);
+
+        // プラグインが入っていなければエラー
+        assert!(
+            obs.call_vendor_request("missing-plugin", "save", &data)
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_play_vlc_source() {
+        let mock = MockObs::start().await;
+        let obs = connect(&mock).await;
+
+        // 2回目は既存のソースを使う
+        obs.init_vlc_source().await.unwrap();
+        obs.init_vlc_source().await.unwrap();
+        assert_eq!(mock.requests_of("CreateInput").len(), 1);
+        assert!(!mock.state.lock().unwrap().scene_items[0].enabled);
+
+        // トリムが無ければまとめて1つのプレイリストにする
+        let items = [item("a.mkv", None, None), item("b.mkv", None, None)];
+        obs.play_clips(&items).await.unwrap();
+        let settings = mock.requests_of("SetInputSettings");
+        assert_eq!(settings.len(), 1);
+        let playlist = settings[0]["inputSettings"]["playlist"].as_array().unwrap();
+        assert_eq!(playlist.len(), 2);
+        assert_eq!(playlist[0]["value"], "a.mkv");
+        assert!(mock.state.lock().unwrap().scene_items[0].enabled);
+        assert!(
+            mock.request_types()
+                .ends_with(&["SetSceneItemEnabled".to_string()])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_play_trimmed_clips() {
+        let mock = MockObs::start().await;
+        let obs = connect(&mock).await;
+        obs.init_vlc_source().await.unwrap();
+
+        // 最初のクリップをin点から再生したら、終わるのを待たずに返る
+        let items = [
+            item("a.mkv", Some(1.0), Some(2.0)),
+            item("b.mkv", None, Some(3.0)),
+        ];
+        obs.play_clips(&items).await.unwrap();
+        assert_eq!(played(&mock), ["a.mkv"]);
+        let seek = mock.requests_of("SetMediaInputCursor");
+        assert_eq!(seek[0]["mediaCursor"].as_f64(), Some(1000.0));
+
+        // out点に達したら次のクリップに切り替える
+        mock.set_media("OBS_MEDIA_STATE_PLAYING", Some(2000.0));
+        wait_for(|| played(&mock).len() == 2).await;
+        assert_eq!(played(&mock), ["a.mkv", "b.mkv"]);
+
+        // 最後のout点で止める
+        let actions = mock.requests_of("TriggerMediaInputAction").len();
+        mock.set_media("OBS_MEDIA_STATE_PLAYING", Some(3000.0));
+        wait_for(|| mock.requests_of("TriggerMediaInputAction").len() > actions).await;
+        assert_eq!(
+            last_media_action(&mock).as_deref(),
+            Some("OBS_WEBSOCKET_MEDIA_INPUT_ACTION_STOP")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stop_trimmed_playlist() {
+        let mock = MockObs::start().await;
+        let obs = connect(&mock).await;
+        obs.init_vlc_source().await.unwrap();
+
+        let items = [
+            item("a.mkv", None, Some(2.0)),
+            item("b.mkv", None, Some(2.0)),
+        ];
+        obs.play_clips(&items).await.unwrap();
+        obs.stop_media().await.unwrap();
+        assert_eq!(
+            last_media_action(&mock).as_deref(),
+            Some("OBS_WEBSOCKET_MEDIA_INPUT_ACTION_STOP")
+        );
+
+        // 止めた後にout点を過ぎても次のクリップは始まらない
+        mock.set_media("OBS_MEDIA_STATE_PLAYING", Some(2000.0));
+        tokio::time::sleep(MEDIA_POLL_INTERVAL * 5).await;
+        assert_eq!(played(&mock), ["a.mkv"]);
+    }
+
+    #[tokio::test]
+    async fn test_paused_playlist_times_out() {
+        let mock = MockObs::start().await;
+        let mut obs = connect(&mock).await;
+        obs.playback_grace = std::time::Duration::from_millis(300);
+        obs.init_vlc_source().await.unwrap();
+
+        let items = [
+            item("a.mkv", None, Some(0.2)),
+            item("b.mkv", None, Some(0.2)),
+        ];
+        obs.play_clips(&items).await.unwrap();
+        // OBSで一時停止されたまま残り時間と猶予が過ぎたら切り替えを諦める
+        mock.set_media("OBS_MEDIA_STATE_PAUSED", Some(0.0));
+        tokio::time::sleep(obs.playback_grace + std::time::Duration::from_millis(500)).await;
+
+        mock.set_media("OBS_MEDIA_STATE_PLAYING", Some(1000.0));
+        tokio::time::sleep(MEDIA_POLL_INTERVAL * 5).await;
+        assert_eq!(played(&mock), ["a.mkv"]);
+    }
+}
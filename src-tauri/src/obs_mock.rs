@@ -0,0 +1,339 @@
+// テスト用のobs-websocket v5の偽サーバー
+// obs.rsで使っているリクエストとReplayBufferSavedイベントだけを実装する
+use std::sync::{Arc, Mutex};
+
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{Value, json};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast;
+use tokio_tungstenite::tungstenite::Message;
+
+const SCENE_NAME: &str = "Scene";
+const SCENE_UUID: &str = "00000000-0000-0000-0000-000000000001";
+
+#[derive(Debug, Clone)]
+pub struct MockInput {
+    pub name: String,
+    pub kind: String,
+    pub uuid: String,
+    pub settings: Value,
+}
+
+#[derive(Debug, Clone)]
+pub struct MockSceneItem {
+    pub id: i64,
+    pub source_name: String,
+    pub source_uuid: String,
+    pub enabled: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct MockState {
+    // 受け取ったリクエスト (requestType, requestData)
+    pub requests: Vec<(String, Value)>,
+    pub replay_buffer_active: bool,
+    pub inputs: Vec<MockInput>,
+    pub scene_items: Vec<MockSceneItem>,
+    pub saved_count: u32,
+    // Source Recordフィルタのリプレイバッファを動かしているソース
+    pub source_record_active: Vec<String>,
+    // GetMediaInputStatusで返す状態 (OBS_MEDIA_STATE_*) と再生位置(ミリ秒)
+    // VLCソースのプレイリストを設定すると先頭から再生中になる
+    pub media_state: String,
+    pub media_cursor_ms: Option<f64>,
+    // GetRecordDirectoryで返す録画フォルダ
+    pub record_directory: String,
+}
+
+pub struct MockObs {
+    pub port: u16,
+    pub state: Arc<Mutex<MockState>>,
+}
+
+impl MockObs {
+    pub async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let state = Arc::new(Mutex::new(MockState {
+            media_state: "OBS_MEDIA_STATE_ENDED".to_string(),
+            ..MockState::default()
+        }));
+        let (events, _) = broadcast::channel(32);
+
+        let accept_state = state.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(handle_connection(
+                    stream,
+                    accept_state.clone(),
+                    events.clone(),
+                ));
+            }
+        });
+
+        Self { port, state }
+    }
+
+    // 受け取ったリクエストの種類を順番に返す
+    pub fn request_types(&self) -> Vec<String> {
+        let state = self.state.lock().unwrap();
+        state.requests.iter().map(|(t, _)| t.clone()).collect()
+    }
+
+    // 再生中のメディアの状態を変える(OBSで一時停止・シークしたときなど)
+    pub fn set_media(&self, media_state: &str, cursor_ms: Option<f64>) {
+        let mut state = self.state.lock().unwrap();
+        state.media_state = media_state.to_string();
+        state.media_cursor_ms = cursor_ms;
+    }
+
+    pub fn requests_of(&self, request_type: &str) -> Vec<Value> {
+        let state = self.state.lock().unwrap();
+        state
+            .requests
+            .iter()
+            .filter(|(t, _)| t == request_type)
+            .map(|(_, d)| d.clone())
+            .collect()
+    }
+}
+
+async fn handle_connection(
+    stream: TcpStream,
+    state: Arc<Mutex<MockState>>,
+    events: broadcast::Sender<Value>,
+) {
+    let Ok(ws) = tokio_tungstenite::accept_async(stream).await else {
+        return;
+    };
+    let (mut sink, mut stream) = ws.split();
+    let hello = json!({"op": 0, "d": {"obsWebSocketVersion": "5.5.0", "rpcVersion": 1}});
+    if sink.send(Message::text(hello.to_string())).await.is_err() {
+        return;
+    }
+
+    let mut event_rx = events.subscribe();
+    loop {
+        tokio::select! {
+            msg = stream.next() => {
+                let Some(Ok(msg)) = msg else { return };
+                let Ok(text) = msg.to_text() else { continue };
+                let Ok(value) = serde_json::from_str::<Value>(text) else { continue };
+                let reply = match value["op"].as_u64() {
+                    // Identify
+                    Some(1) => json!({"op": 2, "d": {"negotiatedRpcVersion": 1}}),
+                    // Request
+                    Some(6) => handle_request(&value["d"], &state, &events),
+                    _ => continue,
+                };
+                if sink.send(Message::text(reply.to_string())).await.is_err() {
+                    return;
+                }
+            }
+            event = event_rx.recv() => {
+                let Ok(event) = event else { continue };
+                if sink.send(Message::text(event.to_string())).await.is_err() {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+fn handle_request(d: &Value, state: &Mutex<MockState>, events: &broadcast::Sender<Value>) -> Value {
+    let request_type = d["requestType"].as_str().unwrap_or_default().to_string();
+    let request_id = d["requestId"].clone();
+    let data = d.get("requestData").cloned().unwrap_or(Value::Null);
+    let mut state = state.lock().unwrap();
+    state.requests.push((request_type.clone(), data.clone()));
+
+    let result: Result<Value, (u16, &str)> = match request_type.as_str() {
+        "GetVersion" => Ok(json!({
+            "obsVersion": "30.2.0",
+            "obsWebSocketVersion": "5.5.0",
+            "rpcVersion": 1,
+            "availableRequests": [],
+            "supportedImageFormats": [],
+            "platform": "windows",
+            "platformDescription": "mock",
+        })),
+        "GetReplayBufferStatus" => Ok(json!({"outputActive": state.replay_buffer_active})),
+        "GetRecordDirectory" => Ok(json!({"recordDirectory": state.record_directory})),
+        "StartReplayBuffer" => {
+            state.replay_buffer_active = true;
+            Ok(Value::Null)
+        }
+        "SaveReplayBuffer" => {
+            if state.replay_buffer_active {
+                state.saved_count += 1;
+                let path = format!("C:/replays/Replay {}.mkv", state.saved_count);
+                let _ = events.send(json!({
+                    "op": 5,
+                    "d": {
+                        "eventType": "ReplayBufferSaved",
+                        "eventIntent": 64,
+                        "eventData": {"savedReplayPath": path},
+                    }
+                }));
+                Ok(Value::Null)
+            } else {
+                Err((501, "replay buffer is not active"))
+            }
+        }
+        "GetInputList" => {
+            let kind = data["inputKind"].as_str();
+            let inputs: Vec<Value> = state
+                .inputs
+                .iter()
+                .filter(|i| kind.is_none_or(|k| k == i.kind))
+                .map(|i| {
+                    json!({
+                        "inputName": i.name,
+                        "inputUuid": i.uuid,
+                        "inputKind": i.kind,
+                        "unversionedInputKind": i.kind,
+                    })
+                })
+                .collect();
+            Ok(json!({"inputs": inputs}))
+        }
+        "CreateInput" => {
+            let index = state.inputs.len() + 1;
+            let uuid = format!("00000000-0000-0000-0001-{:012}", index);
+            let name = data["inputName"].as_str().unwrap_or_default().to_string();
+            state.inputs.push(MockInput {
+                name: name.clone(),
+                kind: data["inputKind"].as_str().unwrap_or_default().to_string(),
+                uuid: uuid.clone(),
+                settings: data["inputSettings"].clone(),
+            });
+            let id = state.scene_items.len() as i64 + 1;
+            state.scene_items.push(MockSceneItem {
+                id,
+                source_name: name,
+                source_uuid: uuid.clone(),
+                enabled: data["sceneItemEnabled"].as_bool().unwrap_or(true),
+            });
+            Ok(json!({"inputUuid": uuid, "sceneItemId": id}))
+        }
+        "SetInputSettings" => {
+            let name = data["inputName"].as_str().unwrap_or_default();
+            match state.inputs.iter_mut().find(|i| i.name == name) {
+                Some(input) => {
+                    input.settings = data["inputSettings"].clone();
+                    if input.kind == "vlc_source" {
+                        state.media_state = "OBS_MEDIA_STATE_PLAYING".to_string();
+                        state.media_cursor_ms = Some(0.0);
+                    }
+                    Ok(Value::Null)
+                }
+                None => Err((600, "input not found")),
+            }
+        }
+        "GetSceneItemList" => {
+            let items: Vec<Value> = state
+                .scene_items
+                .iter()
+                .enumerate()
+                .map(|(index, item)| {
+                    json!({
+                        "sceneItemId": item.id,
+                        "sceneItemIndex": index,
+                        "sourceName": item.source_name,
+                        "sourceUuid": item.source_uuid,
+                        "sourceType": "OBS_SOURCE_TYPE_INPUT",
+                        "inputKind": null,
+                        "isGroup": null,
+                        "sceneItemEnabled": item.enabled,
+                    })
+                })
+                .collect();
+            Ok(json!({"sceneItems": items}))
+        }
+        "SetSceneItemEnabled" => {
+            let id = data["sceneItemId"].as_i64();
+            let enabled = data["sceneItemEnabled"].as_bool().unwrap_or_default();
+            match state.scene_items.iter_mut().find(|i| Some(i.id) == id) {
+                Some(item) => {
+                    item.enabled = enabled;
+                    Ok(Value::Null)
+                }
+                None => Err((600, "scene item not found")),
+            }
+        }
+        "GetCurrentProgramScene" => Ok(json!({
+            "sceneName": SCENE_NAME,
+            "sceneUuid": SCENE_UUID,
+            "currentProgramSceneName": SCENE_NAME,
+            "currentProgramSceneUuid": SCENE_UUID,
+        })),
+        "GetMediaInputStatus" => Ok(json!({
+            "mediaState": state.media_state,
+            "mediaDuration": null,
+            "mediaCursor": state.media_cursor_ms,
+        })),
+        "SetMediaInputCursor" => {
+            state.media_cursor_ms = data["mediaCursor"].as_f64();
+            Ok(Value::Null)
+        }
+        "TriggerMediaInputAction" => {
+            let media_state = match data["mediaAction"].as_str() {
+                Some("OBS_WEBSOCKET_MEDIA_INPUT_ACTION_STOP") => Some("OBS_MEDIA_STATE_STOPPED"),
+                Some("OBS_WEBSOCKET_MEDIA_INPUT_ACTION_PAUSE") => Some("OBS_MEDIA_STATE_PAUSED"),
+                Some("OBS_WEBSOCKET_MEDIA_INPUT_ACTION_PLAY") => Some("OBS_MEDIA_STATE_PLAYING"),
+                _ => None,
+            };
+            match media_state {
+                Some(media_state) => {
+                    state.media_state = media_state.to_string();
+                    Ok(Value::Null)
+                }
+                None => Err((400, "unsupported media action")),
+            }
+        }
+        // Source Recordプラグインのベンダーリクエストだけ受け付ける
+        "CallVendorRequest" => match data["vendorName"].as_str() {
+            Some("source-record") => {
+                let source = data["requestData"]["source"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string();
+                let active = state.source_record_active.contains(&source);
+                match data["requestType"].as_str() {
+                    Some("replay_buffer_save") if !active => {
+                        Err((702, "replay buffer is not active"))
+                    }
+                    request_type => {
+                        if request_type == Some("replay_buffer_start") && !active {
+                            state.source_record_active.push(source);
+                        }
+                        Ok(json!({
+                            "vendorName": "source-record",
+                            "requestType": data["requestType"],
+                            "responseData": {},
+                        }))
+                    }
+                }
+            }
+            _ => Err((600, "vendor not found")),
+        },
+        _ => Err((204, "unknown request type")),
+    };
+
+    let (status, response_data) = match result {
+        Ok(data) => (json!({"result": true, "code": 100}), data),
+        Err((code, comment)) => (
+            json!({"result": false, "code": code, "comment": comment}),
+            Value::Null,
+        ),
+    };
+    let mut d = json!({
+        "requestType": request_type,
+        "requestId": request_id,
+        "requestStatus": status,
+    });
+    if !response_data.is_null() {
+        d["responseData"] = response_data;
+    }
+    json!({"op": 7, "d": d})
+}
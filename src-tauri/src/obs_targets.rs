@@ -0,0 +1,188 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use crate::backend::{Backends, CaptureTarget};
+use crate::clip_catalog::DEFAULT_ANGLE;
+use crate::obs::Obs;
+use crate::source_record::{SourceRecordClip, SourceRecordSettings};
+
+// OBSごとの役割。captureはリプレイバッファで録画し、playbackはVLCソースで再生する
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ObsRole {
+    Capture,
+    Playback,
+    Both,
+}
+
+impl ObsRole {
+    pub fn captures(self) -> bool {
+        matches!(self, Self::Capture | Self::Both)
+    }
+
+    pub fn plays(self) -> bool {
+        matches!(self, Self::Playback | Self::Both)
+    }
+}
+
+// 接続するOBS(番組用のOBSやISOカメラ用のOBSなど)
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ObsTarget {
+    pub name: String,
+    pub host: String,
+    pub port: u16,
+    pub password: Option<String>,
+    pub role: ObsRole,
+}
+
+impl ObsTarget {
+    // 1台のOBSで録画も再生もする従来の構成
+    pub fn single(host: String, port: u16, password: Option<String>) -> Self {
+        Self {
+            name: DEFAULT_ANGLE.to_string(),
+            host,
+            port,
+            password,
+            role: ObsRole::Both,
+        }
+    }
+}
+
+// 名前が重複せず、録画するOBSが1つ以上、再生するOBSがちょうど1つあること
+pub fn validate(targets: &[ObsTarget]) -> Result<(), String> {
+    let mut names = HashSet::new();
+    for target in targets {
+        if target.name.trim().is_empty() {
+            return Err("OBSの名前を入力してください".to_string());
+        }
+        if !names.insert(target.name.as_str()) {
+            return Err(format!("OBSの名前{}が重複しています", target.name));
+        }
+    }
+    if !targets.iter().any(|t| t.role.captures()) {
+        return Err("録画するOBSを1つ以上指定してください".to_string());
+    }
+    if targets.iter().filter(|t| t.role.plays()).count() != 1 {
+        return Err("再生するOBSを1つだけ指定してください".to_string());
+    }
+    Ok(())
+}
+
+// 接続して役割に必要なリプレイバッファ・VLCソースを準備する
+pub async fn connect(target: &ObsTarget) -> Result<Obs, String> {
+    let mut obs = Obs::new();
+    obs.connect(&target.host, target.port, target.password.as_deref())
+        .await
+        .map_err(|e| format!("{}: OBS接続に失敗しました: {}", target.name, e))?;
+    if target.role.captures() {
+        obs.set_replay_buffer()
+            .await
+            .map_err(|e| format!("{}: Failed to set replay buffer: {}", target.name, e))?;
+    }
+    if target.role.plays() {
+        obs.init_vlc_source()
+            .await
+            .map_err(|e| format!("{}: Failed to init VLC source: {}", target.name, e))?;
+    }
+    info!(
+        "Connected to OBS {} at {}:{} as {:?}",
+        target.name, target.host, target.port, target.role
+    );
+    Ok(obs)
+}
+
+// 接続済みのOBSから録画先・再生先をまとめる。表示(スコアボード)は再生するOBSに出す
+// Source Recordのアングルはリプレイバッファより後に保存を頼むよう録画先の最後に並べる
+pub fn backends(
+    connected: Vec<(ObsTarget, Obs)>,
+    source_record: &SourceRecordSettings,
+) -> Result<Backends, String> {
+    let mut capture = Vec::new();
+    let mut program = None;
+    let mut instances = Vec::new();
+    for (target, obs) in connected {
+        let obs = Arc::new(obs);
+        if target.role.captures() {
+            capture.push(CaptureTarget {
+                name: target.name.clone(),
+                backend: obs.clone(),
+            });
+        }
+        if target.role.plays() {
+            program = Some(obs.clone());
+        }
+        instances.push((target.name, obs));
+    }
+    if source_record.enabled {
+        for angle in &source_record.angles {
+            if instances.iter().any(|(name, _)| *name == angle.name) {
+                return Err(format!(
+                    "アングル名{}がOBSの名前と重複しています",
+                    angle.name
+                ));
+            }
+            let (_, obs) = instances
+                .iter()
+                .find(|(name, _)| *name == angle.obs)
+                .ok_or_else(|| format!("{}のOBS {}に接続していません", angle.name, angle.obs))?;
+            capture.push(CaptureTarget {
+                name: angle.name.clone(),
+                backend: Arc::new(SourceRecordClip::new(obs.clone(), angle.clone())),
+            });
+        }
+    }
+    let program = program.ok_or("再生するOBSがありません")?;
+    Ok(Backends {
+        capture,
+        playback: program.clone(),
+        overlay: program,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn target(name: &str, role: ObsRole) -> ObsTarget {
+        ObsTarget {
+            name: name.to_string(),
+            host: "localhost".to_string(),
+            port: 4455,
+            password: None,
+            role,
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(validate(&[ObsTarget::single("localhost".to_string(), 4455, None)]).is_ok());
+        assert!(
+            validate(&[
+                target("program", ObsRole::Both),
+                target("iso", ObsRole::Capture),
+            ])
+            .is_ok()
+        );
+        // 録画するOBSが無い
+        assert!(validate(&[target("program", ObsRole::Playback)]).is_err());
+        // 再生するOBSが2つある
+        assert!(
+            validate(&[
+                target("program", ObsRole::Both),
+                target("iso", ObsRole::Playback),
+            ])
+            .is_err()
+        );
+        assert!(
+            validate(&[
+                target("program", ObsRole::Both),
+                target("program", ObsRole::Capture),
+            ])
+            .is_err()
+        );
+    }
+}
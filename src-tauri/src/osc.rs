@@ -0,0 +1,229 @@
+use std::net::SocketAddr;
+
+use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
+use tokio::net::UdpSocket;
+
+use crate::AppState;
+use crate::api_server::{self, RemoteAction};
+
+// アドレスの先頭。/replay/play/latest /replay/play/last/3 /replay/clip /replay/stop
+const ADDRESS_PREFIX: &str = "/replay";
+
+// Companion/TouchOSCなどからのOSCを受ける設定
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct OscSettings {
+    pub enabled: bool,
+    pub port: u16,
+}
+
+impl Default for OscSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 12346,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum OscArg {
+    Int(i32),
+    Float(f32),
+    Str(String),
+    Bool(bool),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OscMessage {
+    pub address: String,
+    pub args: Vec<OscArg>,
+}
+
+// 4バイト境界までnullで埋められた文字列を読む
+fn read_string(buf: &[u8], pos: &mut usize) -> Result<String, String> {
+    let rest = buf.get(*pos..).ok_or("truncated packet")?;
+    let len = rest
+        .iter()
+        .position(|&b| b == 0)
+        .ok_or("unterminated string")?;
+    let s = std::str::from_utf8(&rest[..len]).map_err(|e| e.to_string())?;
+    *pos += (len + 4) & !3;
+    Ok(s.to_string())
+}
+
+fn read_i32(buf: &[u8], pos: &mut usize) -> Result<i32, String> {
+    let bytes = buf.get(*pos..*pos + 4).ok_or("truncated packet")?;
+    *pos += 4;
+    Ok(i32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn parse_message(buf: &[u8]) -> Result<OscMessage, String> {
+    let mut pos = 0;
+    let address = read_string(buf, &mut pos)?;
+    // 型タグの無い古い送信元もある
+    if pos >= buf.len() {
+        return Ok(OscMessage {
+            address,
+            args: Vec::new(),
+        });
+    }
+    let tags = read_string(buf, &mut pos)?;
+    let mut args = Vec::new();
+    for tag in tags.strip_prefix(',').ok_or("missing type tags")?.chars() {
+        let arg = match tag {
+            'i' => OscArg::Int(read_i32(buf, &mut pos)?),
+            'f' => OscArg::Float(f32::from_bits(read_i32(buf, &mut pos)? as u32)),
+            's' => OscArg::Str(read_string(buf, &mut pos)?),
+            'T' => OscArg::Bool(true),
+            'F' => OscArg::Bool(false),
+            'N' | 'I' => continue,
+            _ => return Err(format!("unsupported type tag: {}", tag)),
+        };
+        args.push(arg);
+    }
+    Ok(OscMessage { address, args })
+}
+
+// バンドルは中のメッセージを順に取り出す(タイムタグは無視してすぐ実行する)
+pub fn parse_packet(buf: &[u8]) -> Result<Vec<OscMessage>, String> {
+    let Some(mut rest) = buf.strip_prefix(b"#bundle\0") else {
+        return Ok(vec![parse_message(buf)?]);
+    };
+    rest = rest.get(8..).ok_or("truncated bundle")?;
+    let mut messages = Vec::new();
+    while !rest.is_empty() {
+        let mut pos = 0;
+        let size = usize::try_from(read_i32(rest, &mut pos)?).map_err(|e| e.to_string())?;
+        let element = rest.get(4..4 + size).ok_or("truncated bundle element")?;
+        messages.extend(parse_packet(element)?);
+        rest = &rest[4 + size..];
+    }
+    Ok(messages)
+}
+
+// ボタンを離したときの0/falseは無視する
+fn is_release(msg: &OscMessage) -> bool {
+    match msg.args.first() {
+        Some(OscArg::Int(0)) | Some(OscArg::Bool(false)) => true,
+        Some(OscArg::Float(v)) => *v == 0.0,
+        _ => false,
+    }
+}
+
+pub fn action_for(msg: &OscMessage) -> Option<RemoteAction> {
+    if is_release(msg) {
+        return None;
+    }
+    let path = msg.address.strip_prefix(ADDRESS_PREFIX)?;
+    // /replay/play/last は引数で個数を指定してもよい
+    if path == "/play/last"
+        && let Some(OscArg::Int(n)) = msg.args.first()
+    {
+        return RemoteAction::from_path(&format!("/play/last/{}", n));
+    }
+    RemoteAction::from_path(path)
+}
+
+pub struct OscServer {
+    pub addr: SocketAddr,
+    pub handle: tokio::task::JoinHandle<()>,
+}
+
+impl OscServer {
+    // UDPなので接続ごとのタスクは無く、受信タスクを止めればソケットも閉じる
+    pub fn stop(self) {
+        self.handle.abort();
+    }
+}
+
+pub async fn start(settings: &OscSettings, state: AppState) -> Result<OscServer, String> {
+    let sock = UdpSocket::bind(("0.0.0.0", settings.port))
+        .await
+        .map_err(|e| format!("Failed to bind port {}: {}", settings.port, e))?;
+    let addr = sock.local_addr().map_err(|e| e.to_string())?;
+    info!("OSC listening on {}", addr);
+
+    let handle = tokio::spawn(async move {
+        let mut buf = [0; 4096];
+        loop {
+            let (size, peer) = match sock.recv_from(&mut buf).await {
+                Ok(received) => received,
+                Err(e) => {
+                    error!("Failed to receive OSC packet: {}", e);
+                    continue;
+                }
+            };
+            let messages = match parse_packet(&buf[..size]) {
+                Ok(messages) => messages,
+                Err(e) => {
+                    warn!("Invalid OSC packet from {}: {}", peer, e);
+                    continue;
+                }
+            };
+            for msg in messages {
+                let Some(action) = action_for(&msg) else {
+                    debug!("Ignored OSC message {:?}", msg);
+                    continue;
+                };
+                if let Err(e) = api_server::perform(&state, action).await {
+                    error!("OSC {} failed: {}", msg.address, e);
+                }
+            }
+        }
+    });
+    Ok(OscServer { addr, handle })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn padded(s: &str) -> Vec<u8> {
+        let mut bytes = s.as_bytes().to_vec();
+        bytes.resize((s.len() + 4) & !3, 0);
+        bytes
+    }
+
+    fn message(address: &str, tags: &str, data: &[u8]) -> Vec<u8> {
+        let mut packet = padded(address);
+        packet.extend(padded(tags));
+        packet.extend_from_slice(data);
+        packet
+    }
+
+    #[test]
+    fn test_parse_packet() {
+        let packet = message("/replay/play/last", ",i", &3i32.to_be_bytes());
+        let msgs = parse_packet(&packet).unwrap();
+        assert_eq!(msgs[0].address, "/replay/play/last");
+        assert_eq!(msgs[0].args, vec![OscArg::Int(3)]);
+        assert_eq!(action_for(&msgs[0]), Some(RemoteAction::PlayLatest(3)));
+
+        // ボタンを離したときの0は無視する
+        let release = message("/replay/clip", ",f", &0f32.to_be_bytes());
+        assert_eq!(action_for(&parse_packet(&release).unwrap()[0]), None);
+
+        let mut bundle = padded("#bundle");
+        bundle.extend_from_slice(&1u64.to_be_bytes());
+        for msg in [
+            message("/replay/stop", ",", &[]),
+            padded("/replay/play/latest"),
+        ] {
+            bundle.extend_from_slice(&(msg.len() as i32).to_be_bytes());
+            bundle.extend(msg);
+        }
+        let actions: Vec<_> = parse_packet(&bundle)
+            .unwrap()
+            .iter()
+            .map(action_for)
+            .collect();
+        assert_eq!(
+            actions,
+            vec![Some(RemoteAction::Stop), Some(RemoteAction::PlayLatest(1))]
+        );
+
+        assert!(parse_packet(&message("/replay/clip", ",i", &[0, 0])).is_err());
+    }
+}
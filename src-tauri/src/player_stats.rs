@@ -0,0 +1,433 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::match_state::{self, MatchState, Side};
+use crate::mugi_schema::{self, _Stats, Boost, Demolished, MugiCmd, Player, Score, SubScore};
+
+// 1人または1チーム分の成績
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct StatLine {
+    pub score: u32,
+    pub goals: u32,
+    pub assists: u32,
+    pub saves: u32,
+    pub shots: u32,
+    pub demos: u32,
+    pub touches: u32,
+}
+
+impl StatLine {
+    fn from_stats(stats: &_Stats) -> Self {
+        Self {
+            score: stats.scores,
+            goals: stats.goals,
+            assists: stats.assists,
+            saves: stats.saves,
+            shots: stats.shots,
+            demos: stats.demos,
+            touches: stats.ball_touches,
+        }
+    }
+
+    fn add(&mut self, other: &StatLine) {
+        self.score += other.score;
+        self.goals += other.goals;
+        self.assists += other.assists;
+        self.saves += other.saves;
+        self.shots += other.shots;
+        self.demos += other.demos;
+        self.touches += other.touches;
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PlayerStats {
+    pub name: String,
+    pub team: Option<Side>,
+    #[serde(flatten)]
+    pub stats: StatLine,
+    // デモされた回数
+    pub demolished: u32,
+    // 現在のブースト量(0-100)。シリーズの集計では持たない
+    pub boost: Option<u32>,
+}
+
+impl PlayerStats {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            team: None,
+            stats: StatLine::default(),
+            demolished: 0,
+            boost: None,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TeamStats {
+    pub name: String,
+    #[serde(flatten)]
+    pub stats: StatLine,
+}
+
+// 観戦中のプレイヤーの成績(subScore/score)
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct FocusedStats {
+    pub score: u32,
+    pub goals: u32,
+    pub shots: u32,
+    pub assists: u32,
+    pub saves: u32,
+}
+
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct GameStats {
+    pub match_id: Option<String>,
+    pub game_number: u32,
+    pub players: Vec<PlayerStats>,
+    pub blue: TeamStats,
+    pub orange: TeamStats,
+    pub focused: FocusedStats,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct SeriesStats {
+    pub games: u32,
+    pub players: Vec<PlayerStats>,
+    // チーム名ごとの合計。試合ごとに色が入れ替わっても同じチームにまとめる
+    pub teams: Vec<TeamStats>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct StatsSnapshot {
+    pub game: GameStats,
+    pub series: SeriesStats,
+}
+
+// 統計ファイルの書き出し設定。OBSのテキストソースから読み込む
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct StatsOutputSettings {
+    pub enabled: bool,
+    pub dir: Option<PathBuf>,
+}
+
+// Mugiの統計系コマンドから現在の試合とシリーズの成績を集計する
+#[derive(Debug, Default)]
+pub struct StatsAggregator {
+    // playerTableの並び。boost/demolishedのindexに対応する
+    player_table: Vec<String>,
+    game: GameStats,
+    finished: Vec<GameStats>,
+}
+
+fn side_of_index(team: u32) -> Option<Side> {
+    match team {
+        0 => Some(Side::Blue),
+        1 => Some(Side::Orange),
+        _ => None,
+    }
+}
+
+impl StatsAggregator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // match_stateは適用済みの試合状況。matchIdが変わったら新しい試合として集計する
+    // 表示の更新が必要な変化があればtrueを返す(ブースト量だけの変化は含めない)
+    pub fn apply(&mut self, cmd: &MugiCmd, msg: &str, match_state: &MatchState) -> Result<bool> {
+        if match_state.match_id != self.game.match_id {
+            self.start_game(match_state);
+        }
+        let changed = match cmd {
+            MugiCmd::TeamNames => {
+                self.game.blue.name = match_state.blue_name.clone();
+                self.game.orange.name = match_state.orange_name.clone();
+                true
+            }
+            MugiCmd::PlayerTable | MugiCmd::DisplayNames => {
+                let Some(names) = mugi_schema::parse_data::<Vec<String>>(msg)? else {
+                    return Ok(false);
+                };
+                for name in &names {
+                    self.player_mut(name);
+                }
+                self.player_table = names;
+                true
+            }
+            MugiCmd::Player => {
+                let Some(data) = mugi_schema::parse_data::<Player>(msg)? else {
+                    return Ok(false);
+                };
+                if self.player_table.len() <= data.player_index {
+                    self.player_table
+                        .resize(data.player_index + 1, String::new());
+                }
+                self.player_table[data.player_index] = data.player_name.clone();
+                let team = match_state.side_of(&data.team);
+                let player = self.player_mut(&data.player_name);
+                player.team = team.or(player.team);
+                true
+            }
+            MugiCmd::Stats => {
+                let Some(stats) = mugi_schema::parse_data::<Vec<_Stats>>(msg)? else {
+                    return Ok(false);
+                };
+                for s in &stats {
+                    let player = self.player_mut(&s.id);
+                    player.stats = StatLine::from_stats(s);
+                    player.team = side_of_index(s.teams).or(player.team);
+                }
+                true
+            }
+            MugiCmd::Demolished => {
+                let Some(data) = mugi_schema::parse_data::<Demolished>(msg)? else {
+                    return Ok(false);
+                };
+                match self.player_table.get(data.victim_index as usize).cloned() {
+                    Some(name) if !name.is_empty() => {
+                        self.player_mut(&name).demolished += 1;
+                        true
+                    }
+                    _ => false,
+                }
+            }
+            MugiCmd::Boost => {
+                let Some(data) = mugi_schema::parse_data::<Boost>(msg)? else {
+                    return Ok(false);
+                };
+                if let Some(name) = self.player_table.get(data.index).cloned()
+                    && !name.is_empty()
+                {
+                    self.player_mut(&name).boost = Some(data.boost);
+                }
+                false
+            }
+            MugiCmd::SubScore => {
+                let Some(data) = mugi_schema::parse_data::<SubScore>(msg)? else {
+                    return Ok(false);
+                };
+                let focused = &mut self.game.focused;
+                focused.goals = data.goals;
+                focused.shots = data.shots;
+                focused.assists = data.assists;
+                focused.saves = data.saves;
+                true
+            }
+            MugiCmd::Score => {
+                let Some(data) = mugi_schema::parse_data::<Score>(msg)? else {
+                    return Ok(false);
+                };
+                self.game.focused.score = data.score;
+                true
+            }
+            _ => false,
+        };
+        if changed {
+            self.update_team_totals();
+        }
+        Ok(changed)
+    }
+
+    pub fn snapshot(&self) -> StatsSnapshot {
+        StatsSnapshot {
+            game: self.game.clone(),
+            series: self.series(),
+        }
+    }
+
+    // 終わった試合の集計を捨てる。現在の試合は残す
+    pub fn reset_series(&mut self) {
+        self.finished.clear();
+    }
+
+    fn start_game(&mut self, match_state: &MatchState) {
+        let game = std::mem::take(&mut self.game);
+        if game.match_id.is_some() && !game.players.is_empty() {
+            self.finished.push(game);
+        }
+        self.player_table.clear();
+        self.game = GameStats {
+            match_id: match_state.match_id.clone(),
+            game_number: match_state.game_number,
+            blue: TeamStats {
+                name: match_state.blue_name.clone(),
+                ..TeamStats::default()
+            },
+            orange: TeamStats {
+                name: match_state.orange_name.clone(),
+                ..TeamStats::default()
+            },
+            ..GameStats::default()
+        };
+    }
+
+    fn player_mut(&mut self, name: &str) -> &mut PlayerStats {
+        let index = match self.game.players.iter().position(|p| p.name == name) {
+            Some(index) => index,
+            None => {
+                self.game.players.push(PlayerStats::new(name));
+                self.game.players.len() - 1
+            }
+        };
+        &mut self.game.players[index]
+    }
+
+    fn update_team_totals(&mut self) {
+        let mut blue = StatLine::default();
+        let mut orange = StatLine::default();
+        for player in &self.game.players {
+            match player.team {
+                Some(Side::Blue) => blue.add(&player.stats),
+                Some(Side::Orange) => orange.add(&player.stats),
+                None => {}
+            }
+        }
+        self.game.blue.stats = blue;
+        self.game.orange.stats = orange;
+    }
+
+    fn series(&self) -> SeriesStats {
+        let mut series = SeriesStats::default();
+        for game in self.finished.iter().chain(Some(&self.game)) {
+            if game.match_id.is_none() {
+                continue;
+            }
+            series.games += 1;
+            for player in &game.players {
+                match series.players.iter_mut().find(|p| p.name == player.name) {
+                    Some(total) => {
+                        total.stats.add(&player.stats);
+                        total.demolished += player.demolished;
+                        total.team = player.team.or(total.team);
+                    }
+                    None => series.players.push(PlayerStats {
+                        boost: None,
+                        ..player.clone()
+                    }),
+                }
+            }
+            for (side, team) in [(Side::Blue, &game.blue), (Side::Orange, &game.orange)] {
+                let name = match_state::team_key(&team.name, side);
+                match series.teams.iter_mut().find(|t| t.name == name) {
+                    Some(total) => total.stats.add(&team.stats),
+                    None => series.teams.push(TeamStats {
+                        name,
+                        stats: team.stats,
+                    }),
+                }
+            }
+        }
+        series
+    }
+}
+
+// stats.jsonと、テキストソース用に1項目1ファイルで書き出す
+pub fn write_files(dir: &Path, snapshot: &StatsSnapshot) -> io::Result<()> {
+    std::fs::create_dir_all(dir)?;
+    let json = serde_json::to_string_pretty(snapshot)?;
+    std::fs::write(dir.join("stats.json"), json)?;
+
+    let game = &snapshot.game;
+    for (side, prefix, team) in [
+        (Side::Blue, "blue", &game.blue),
+        (Side::Orange, "orange", &game.orange),
+    ] {
+        let stats = &team.stats;
+        let values = [
+            ("name", team.name.clone()),
+            ("goals", stats.goals.to_string()),
+            ("assists", stats.assists.to_string()),
+            ("saves", stats.saves.to_string()),
+            ("shots", stats.shots.to_string()),
+            ("demos", stats.demos.to_string()),
+            ("touches", stats.touches.to_string()),
+        ];
+        for (key, value) in values {
+            std::fs::write(dir.join(format!("{prefix}_{key}.txt")), value)?;
+        }
+
+        let players: Vec<String> = game
+            .players
+            .iter()
+            .filter(|p| p.team == Some(side))
+            .map(|p| {
+                format!(
+                    "{} {}G {}A {}SV {}SH",
+                    p.name, p.stats.goals, p.stats.assists, p.stats.saves, p.stats.shots
+                )
+            })
+            .collect();
+        std::fs::write(
+            dir.join(format!("{prefix}_players.txt")),
+            players.join("\n"),
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn run(aggregator: &mut StatsAggregator, match_state: &mut MatchState, lines: &str) {
+        for msg in lines.lines() {
+            let cmd = mugi_schema::parse_cmd(msg).unwrap();
+            match_state.apply(&cmd, msg).unwrap();
+            aggregator.apply(&cmd, msg, match_state).unwrap();
+        }
+    }
+
+    // 合成したセッション(fixtures/mugi/README.md)なので、期待値は実機ではなくフィクスチャの内容から決めている
+    #[test]
+    fn test_session_stats() {
+        let session = include_str!("../fixtures/mugi/session.jsonl");
+        let mut aggregator = StatsAggregator::new();
+        let mut match_state = MatchState::new();
+        let messages = crate::mugi_capture::capture_messages(session);
+        run(&mut aggregator, &mut match_state, &messages.join("\n"));
+
+        let game = aggregator.snapshot().game;
+        assert_eq!(game.players.len(), 6);
+        let moca = &game.players[0];
+        assert_eq!(moca.team, Some(Side::Blue));
+        assert_eq!(moca.stats.saves, 1);
+        assert_eq!(moca.boost, Some(12));
+        // receiverIndex:3がvictimIndex:1をデモした
+        assert_eq!(game.players[1].demolished, 1);
+        assert_eq!(game.blue.name, "Team Moca");
+        assert_eq!(game.blue.stats.touches, 18);
+        assert_eq!(game.orange.stats.goals, 2);
+        assert_eq!(game.orange.stats.demos, 1);
+        assert_eq!(game.focused.score, 210);
+
+        // 次の試合が始まるとシリーズに積まれる
+        let next = r#"{"cmd":"teamNames","data":{"blue":"Team Mugi","matchId":"NEXT","orange":"Team Moca"}}
+{"cmd":"stats","data":[{"id":"Mugi_1","teams":0,"scores":100,"goals":1,"assists":0,"saves":0,"shots":1,"demos":0,"ballTouches":4}]}"#;
+        run(&mut aggregator, &mut match_state, next);
+        let series = aggregator.snapshot().series;
+        assert_eq!(series.games, 2);
+        let mugi_1 = series.players.iter().find(|p| p.name == "Mugi_1").unwrap();
+        assert_eq!(mugi_1.stats.shots, 3);
+        assert_eq!(mugi_1.stats.touches, 15);
+        assert_eq!(mugi_1.boost, None);
+        let team_mugi = series.teams.iter().find(|t| t.name == "Team Mugi").unwrap();
+        assert_eq!(team_mugi.stats.goals, 3);
+        assert_eq!(team_mugi.stats.touches, 27);
+
+        aggregator.reset_series();
+        assert_eq!(aggregator.snapshot().series.games, 1);
+    }
+}
@@ -0,0 +1,72 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+// 外部ツール(実況の合図、運営の操作など)からのトリガー
+// Mugiのコマンドとは別の型にして、Mugiが同じ名前のコマンドを追加しても混ざらないようにする
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub enum RemoteTrigger {
+    // 手動クリップの保存
+    Clip(ClipRequest),
+}
+
+// clipのdata。labelはクリップに残すメモ(実況のコールなど)
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+pub struct ClipRequest {
+    #[serde(default)]
+    pub label: Option<String>,
+}
+
+// Mugiと同じ{"cmd":..,"data":..}の形で届く
+#[derive(Deserialize)]
+struct TriggerData {
+    cmd: String,
+    #[serde(default)]
+    data: Option<serde_json::Value>,
+}
+
+// トリガーのメッセージを読む。トリガーのコマンドでなければNone
+pub fn parse_trigger(json: &str) -> Result<Option<RemoteTrigger>> {
+    let msg: TriggerData = serde_json::from_str(json)?;
+    let trigger = match msg.cmd.as_str() {
+        "clip" => {
+            let request = match msg.data {
+                Some(data) => serde_json::from_value(data)?,
+                None => ClipRequest::default(),
+            };
+            RemoteTrigger::Clip(request)
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(trigger))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_clip_request() {
+        let msg = include_str!("../fixtures/remote/clip_request.json").trim();
+        assert_eq!(
+            parse_trigger(msg).unwrap(),
+            Some(RemoteTrigger::Clip(ClipRequest {
+                label: Some("caster callout".to_string()),
+            }))
+        );
+        // dataが無ければラベル無しのクリップ
+        assert_eq!(
+            parse_trigger(r#"{"cmd":"clip","data":null}"#).unwrap(),
+            Some(RemoteTrigger::Clip(ClipRequest::default()))
+        );
+        assert!(parse_trigger(r#"{"cmd":"clip","data":{"label":1}}"#).is_err());
+    }
+
+    #[test]
+    fn test_mugi_cmd_is_not_trigger() {
+        assert_eq!(
+            parse_trigger(r#"{"cmd":"scored","data":null}"#).unwrap(),
+            None
+        );
+        assert!(parse_trigger("not json").is_err());
+    }
+}
@@ -0,0 +1,265 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use log::error;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::sync::watch;
+
+use crate::backend::OverlayBackend;
+use crate::match_state::{MatchState, Side};
+use crate::series::Series;
+
+// スコアボード(スコアバグ)の値を流し込むOBSのソース
+// 使えるプレースホルダ: {blue_name} {orange_name} {blue_score} {orange_score}
+// {blue_series} {orange_series} {clock} {last_scorer}
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ScoreboardTarget {
+    pub input: String,
+    // 書き換える設定のキー。テキストソースはtext、ブラウザソースはurl
+    #[serde(default = "default_setting")]
+    pub setting: String,
+    pub template: String,
+}
+
+fn default_setting() -> String {
+    "text".to_string()
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ScoreboardSettings {
+    pub enabled: bool,
+    pub targets: Vec<ScoreboardTarget>,
+}
+
+// テンプレートに埋め込む値
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ScoreboardValues {
+    pub blue_name: String,
+    pub orange_name: String,
+    pub blue_score: u32,
+    pub orange_score: u32,
+    pub blue_series: u32,
+    pub orange_series: u32,
+    pub clock: String,
+    pub last_scorer: String,
+}
+
+impl ScoreboardValues {
+    pub fn from_state(state: &MatchState, series: Option<&Series>) -> Self {
+        let clock = match state.clock {
+            Some(clock) if state.is_overtime => format!("+{}:{:02}", clock / 60, clock % 60),
+            Some(clock) => format!("{}:{:02}", clock / 60, clock % 60),
+            None => String::new(),
+        };
+        Self {
+            blue_name: state.blue_name.clone(),
+            orange_name: state.orange_name.clone(),
+            blue_score: state.blue_goals,
+            orange_score: state.orange_goals,
+            blue_series: series.map_or(0, |s| s.wins_of(state, Side::Blue)),
+            orange_series: series.map_or(0, |s| s.wins_of(state, Side::Orange)),
+            clock,
+            last_scorer: state
+                .last_goal
+                .as_ref()
+                .map(|g| g.score_id.clone())
+                .unwrap_or_default(),
+        }
+    }
+
+    fn placeholder(&self, name: &str) -> Option<String> {
+        let value = match name {
+            "blue_name" => self.blue_name.clone(),
+            "orange_name" => self.orange_name.clone(),
+            "blue_score" => self.blue_score.to_string(),
+            "orange_score" => self.orange_score.to_string(),
+            "blue_series" => self.blue_series.to_string(),
+            "orange_series" => self.orange_series.to_string(),
+            "clock" => self.clock.clone(),
+            "last_scorer" => self.last_scorer.clone(),
+            _ => return None,
+        };
+        Some(value)
+    }
+}
+
+// URLのクエリに入れられるようにエンコードする
+fn encode_url_value(value: &str) -> String {
+    let mut encoded = String::new();
+    for b in value.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(b as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    encoded
+}
+
+pub fn render(target: &ScoreboardTarget, values: &ScoreboardValues) -> String {
+    let is_url = target.setting == "url";
+    let mut rendered = String::new();
+    let mut rest = target.template.as_str();
+    while let Some(start) = rest.find('{') {
+        rendered.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        match after
+            .find('}')
+            .and_then(|end| values.placeholder(&after[..end]).map(|v| (end, v)))
+        {
+            Some((end, value)) if is_url => {
+                rendered.push_str(&encode_url_value(&value));
+                rest = &after[end + 1..];
+            }
+            Some((end, value)) => {
+                rendered.push_str(&value);
+                rest = &after[end + 1..];
+            }
+            // 未知のプレースホルダはそのまま残す
+            None => {
+                rendered.push('{');
+                rest = after;
+            }
+        }
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+// 値が変わるたびにソースを更新する。前回と同じ内容のソースには送らない
+pub fn spawn_scoreboard(
+    overlay: Arc<dyn OverlayBackend>,
+    settings: Arc<RwLock<ScoreboardSettings>>,
+    mut rx: watch::Receiver<ScoreboardValues>,
+) {
+    tokio::spawn(async move {
+        let mut sent: HashMap<(String, String), String> = HashMap::new();
+        while rx.changed().await.is_ok() {
+            let values = rx.borrow_and_update().clone();
+            let settings = settings.read().unwrap().clone();
+            if !settings.enabled {
+                sent.clear();
+                continue;
+            }
+            for target in &settings.targets {
+                let text = render(target, &values);
+                let key = (target.input.clone(), target.setting.clone());
+                if sent.get(&key) == Some(&text) {
+                    continue;
+                }
+                let input_settings = json!({ target.setting.as_str(): text });
+                match overlay
+                    .set_input_settings(&target.input, &input_settings)
+                    .await
+                {
+                    Ok(()) => {
+                        sent.insert(key, text);
+                    }
+                    Err(e) => error!("Failed to update scoreboard source: {}", e),
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::backend::fake::FakeBackend;
+    use crate::mugi_schema::Goals;
+    use crate::series::SeriesManager;
+
+    fn state() -> MatchState {
+        MatchState {
+            match_id: Some("A".to_string()),
+            blue_name: "Team Moca".to_string(),
+            orange_name: "Team Mugi".to_string(),
+            clock: Some(65),
+            blue_goals: 2,
+            orange_goals: 1,
+            last_goal: Some(Goals {
+                team: "blue".to_string(),
+                score_id: "Moca_1".to_string(),
+                assist_id: String::new(),
+            }),
+            ..MatchState::default()
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        let mut series = SeriesManager::new();
+        series.on_team_names(&state());
+        series.record_result(&state());
+        let values = ScoreboardValues::from_state(&state(), series.current());
+        let target = ScoreboardTarget {
+            input: "Bug".to_string(),
+            setting: "text".to_string(),
+            template: "{blue_name} {blue_score}-{orange_score} {orange_name} ({blue_series}-{orange_series}) {clock} {unknown}".to_string(),
+        };
+        assert_eq!(
+            render(&target, &values),
+            "Team Moca 2-1 Team Mugi (1-0) 1:05 {unknown}"
+        );
+
+        let target = ScoreboardTarget {
+            input: "Browser".to_string(),
+            setting: "url".to_string(),
+            template: "http://localhost/bug.html?blue={blue_name}&scorer={last_scorer}".to_string(),
+        };
+        assert_eq!(
+            render(&target, &values),
+            "http://localhost/bug.html?blue=Team%20Moca&scorer=Moca_1"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_spawn_scoreboard_skips_unchanged() {
+        let backend = Arc::new(FakeBackend::default());
+        let settings = Arc::new(RwLock::new(ScoreboardSettings {
+            enabled: true,
+            targets: vec![
+                ScoreboardTarget {
+                    input: "Clock".to_string(),
+                    setting: default_setting(),
+                    template: "{clock}".to_string(),
+                },
+                ScoreboardTarget {
+                    input: "Blue".to_string(),
+                    setting: default_setting(),
+                    template: "{blue_name}".to_string(),
+                },
+            ],
+        }));
+        let (tx, rx) = watch::channel(ScoreboardValues::default());
+        spawn_scoreboard(backend.clone(), settings, rx);
+
+        let mut state = state();
+        // 2つ目の値では時計だけが変わる
+        for (clock, expected) in [(65, 2), (64, 3)] {
+            state.clock = Some(clock);
+            tx.send(ScoreboardValues::from_state(&state, None)).unwrap();
+            for _ in 0..50 {
+                if backend.inputs.lock().unwrap().len() >= expected {
+                    break;
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+        }
+
+        let inputs = backend.inputs.lock().unwrap().clone();
+        assert_eq!(
+            inputs,
+            vec![
+                ("Clock".to_string(), json!({"text": "1:05"})),
+                ("Blue".to_string(), json!({"text": "Team Moca"})),
+                ("Clock".to_string(), json!({"text": "1:04"})),
+            ]
+        );
+    }
+}
@@ -0,0 +1,230 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::match_state::{self, MatchState, Side};
+
+// 複数試合をまとめたシリーズ(Bo5/Bo7など)
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Series {
+    pub id: u64,
+    pub name: String,
+    pub best_of: Option<u32>,
+    // 手動で開始したシリーズはチーム名が変わっても終了するまで続ける
+    pub manual: bool,
+    // 対戦する2チームのキー(名前順)。手動開始直後は空
+    pub teams: Vec<String>,
+    pub match_ids: Vec<String>,
+    pub wins: BTreeMap<String, u32>,
+    pub winner: Option<String>,
+    pub is_ended: bool,
+}
+
+impl Series {
+    // best_ofの過半数の勝利で決着
+    pub fn wins_needed(&self) -> Option<u32> {
+        self.best_of.map(|n| n / 2 + 1)
+    }
+
+    pub fn wins_of(&self, state: &MatchState, side: Side) -> u32 {
+        self.wins
+            .get(&match_state::team_key(state.team_name(side), side))
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+fn team_keys(state: &MatchState) -> Vec<String> {
+    let mut keys = vec![
+        match_state::team_key(&state.blue_name, Side::Blue),
+        match_state::team_key(&state.orange_name, Side::Orange),
+    ];
+    keys.sort();
+    keys
+}
+
+#[derive(Debug, Default)]
+pub struct SeriesManager {
+    current: Option<Series>,
+    history: Vec<Series>,
+    next_id: u64,
+}
+
+impl SeriesManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn current(&self) -> Option<&Series> {
+        self.current.as_ref()
+    }
+
+    pub fn get(&self, id: u64) -> Option<&Series> {
+        self.history
+            .iter()
+            .chain(self.current.as_ref())
+            .find(|s| s.id == id)
+    }
+
+    // 終わったシリーズと現在のシリーズを開始順に返す
+    pub fn list(&self) -> Vec<Series> {
+        self.history
+            .iter()
+            .chain(self.current.as_ref())
+            .cloned()
+            .collect()
+    }
+
+    // 現在のシリーズを終えて新しいシリーズを手動で開始する
+    pub fn start(&mut self, name: Option<String>, best_of: Option<u32>) -> &Series {
+        let id = self.next_id;
+        let name = name
+            .filter(|n| !n.trim().is_empty())
+            .unwrap_or_else(|| format!("Series {}", id + 1));
+        self.begin(Series {
+            id,
+            name,
+            best_of,
+            manual: true,
+            teams: Vec::new(),
+            match_ids: Vec::new(),
+            wins: BTreeMap::new(),
+            winner: None,
+            is_ended: false,
+        })
+    }
+
+    pub fn end(&mut self) -> Option<Series> {
+        let mut series = self.current.take()?;
+        series.is_ended = true;
+        self.history.push(series.clone());
+        Some(series)
+    }
+
+    // teamNamesを受けて試合を現在のシリーズに入れる
+    // 同じチームの対戦なら続け、違えば新しいシリーズを自動で始める
+    // シリーズに変化があればそのシリーズを返す
+    pub fn on_team_names(&mut self, state: &MatchState) -> Option<&Series> {
+        let match_id = state.match_id.clone()?;
+        let keys = team_keys(state);
+        let continues = match &self.current {
+            Some(series) if series.match_ids.contains(&match_id) => return None,
+            Some(series) => !series.is_ended && (series.manual || series.teams == keys),
+            None => false,
+        };
+        if !continues {
+            let id = self.next_id;
+            self.begin(Series {
+                id,
+                name: format!("{} vs {}", keys[0], keys[1]),
+                best_of: None,
+                manual: false,
+                teams: Vec::new(),
+                match_ids: Vec::new(),
+                wins: BTreeMap::new(),
+                winner: None,
+                is_ended: false,
+            });
+        }
+        let series = self.current.as_mut()?;
+        if series.teams.is_empty() {
+            series.teams = keys;
+        }
+        series.match_ids.push(match_id);
+        self.current.as_ref()
+    }
+
+    // 試合終了時に勝ったチームに1勝を加え、決着したらシリーズを終える
+    pub fn record_result(&mut self, state: &MatchState) -> Option<&Series> {
+        let match_id = state.match_id.as_ref()?;
+        let series = self.current.as_mut()?;
+        if series.is_ended || !series.match_ids.contains(match_id) {
+            return None;
+        }
+        let winner = match state.blue_goals.cmp(&state.orange_goals) {
+            std::cmp::Ordering::Greater => Side::Blue,
+            std::cmp::Ordering::Less => Side::Orange,
+            std::cmp::Ordering::Equal => return None,
+        };
+        let key = match_state::team_key(state.team_name(winner), winner);
+        let wins = series.wins.entry(key.clone()).or_default();
+        *wins += 1;
+        let wins = *wins;
+        if series.wins_needed().is_some_and(|needed| wins >= needed) {
+            series.winner = Some(key);
+            series.is_ended = true;
+        }
+        self.current.as_ref()
+    }
+
+    fn begin(&mut self, series: Series) -> &Series {
+        self.end();
+        self.next_id += 1;
+        self.current.insert(series)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn game(match_id: &str, blue: &str, orange: &str, score: (u32, u32)) -> MatchState {
+        MatchState {
+            match_id: Some(match_id.to_string()),
+            blue_name: blue.to_string(),
+            orange_name: orange.to_string(),
+            blue_goals: score.0,
+            orange_goals: score.1,
+            ..MatchState::default()
+        }
+    }
+
+    #[test]
+    fn test_auto_series() {
+        let mut manager = SeriesManager::new();
+        // 色が入れ替わっても同じシリーズ
+        for state in [game("g1", "A", "B", (2, 1)), game("g2", "B", "A", (0, 3))] {
+            manager.on_team_names(&state);
+            assert!(manager.on_team_names(&state).is_none());
+            manager.record_result(&state);
+        }
+        let series = manager.current().unwrap();
+        assert_eq!(series.match_ids, vec!["g1", "g2"]);
+        assert_eq!(series.wins["A"], 2);
+        assert_eq!(
+            series.wins_of(&game("g3", "B", "A", (0, 0)), Side::Orange),
+            2
+        );
+
+        // 別のチームの試合で新しいシリーズになる
+        manager.on_team_names(&game("g3", "C", "D", (0, 0)));
+        assert_eq!(manager.list().len(), 2);
+        assert!(manager.list()[0].is_ended);
+        assert_eq!(manager.current().unwrap().name, "C vs D");
+    }
+
+    #[test]
+    fn test_manual_best_of() {
+        let mut manager = SeriesManager::new();
+        let id = manager.start(Some("Final".to_string()), Some(3)).id;
+        for state in [
+            game("g1", "A", "B", (1, 0)),
+            game("g2", "A", "B", (0, 1)),
+            game("g3", "B", "A", (0, 2)),
+        ] {
+            manager.on_team_names(&state);
+            manager.record_result(&state);
+        }
+        let series = manager.get(id).unwrap();
+        assert_eq!(series.teams, vec!["A", "B"]);
+        assert_eq!(series.winner.as_deref(), Some("A"));
+        assert!(series.is_ended);
+
+        // 決着後の試合は新しいシリーズ
+        manager.on_team_names(&game("g4", "A", "B", (0, 0)));
+        assert_ne!(manager.current().unwrap().id, id);
+        assert!(manager.end().is_some());
+        assert!(manager.current().is_none());
+    }
+}
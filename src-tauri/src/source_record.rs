@@ -0,0 +1,260 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::sync::mpsc::Sender;
+
+use crate::backend::{BackendFuture, ClipBackend};
+use crate::clip_storage::list_videos;
+use crate::obs::Obs;
+
+// Source Recordプラグインのベンダー名
+const VENDOR: &str = "source-record";
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+// 保存を頼んでからファイルが書き終わるまで待つ時間
+const WAIT_TIMEOUT: Duration = Duration::from_secs(30);
+
+// Source Recordフィルタで録画するソース。オーバーレイの無い映像を別アングルとして保存する
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SourceRecordAngle {
+    // カタログでのアングル名(ボールカメラなど)
+    pub name: String,
+    // フィルタを付けたソースがあるOBSの名前
+    pub obs: String,
+    pub source: String,
+    // 省略するとソースに付いているすべてのSource Recordフィルタ
+    #[serde(default)]
+    pub filter: Option<String>,
+    // フィルタのリプレイバッファの保存先
+    pub dir: PathBuf,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct SourceRecordSettings {
+    pub enabled: bool,
+    pub angles: Vec<SourceRecordAngle>,
+}
+
+impl SourceRecordSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        let mut names = HashSet::new();
+        let mut dirs = HashSet::new();
+        for angle in &self.angles {
+            if angle.name.trim().is_empty() || angle.source.trim().is_empty() {
+                return Err("アングル名とソース名を入力してください".to_string());
+            }
+            if !names.insert(angle.name.as_str()) {
+                return Err(format!("アングル名{}が重複しています", angle.name));
+            }
+            // 保存先に増えたファイルでクリップを見分けるので、アングルごとに分ける
+            if !dirs.insert(angle.dir.as_path()) {
+                return Err(format!("{}の保存先が他のアングルと同じです", angle.name));
+            }
+        }
+        Ok(())
+    }
+}
+
+// knownに無い動画が現れ、サイズが変わらなくなる(書き終わる)まで待つ
+async fn wait_for_new_file(
+    dir: &Path,
+    known: &HashSet<PathBuf>,
+    timeout: Duration,
+    interval: Duration,
+) -> Option<PathBuf> {
+    let deadline = tokio::time::Instant::now() + timeout;
+    let mut last: Option<(PathBuf, u64)> = None;
+    while tokio::time::Instant::now() < deadline {
+        let found = list_videos(dir)
+            .into_iter()
+            .find(|(path, _)| !known.contains(path));
+        match (&found, &last) {
+            (Some((path, size)), Some(prev)) if *size > 0 && (path, size) == (&prev.0, &prev.1) => {
+                return Some(path.clone());
+            }
+            _ => last = found,
+        }
+        tokio::time::sleep(interval).await;
+    }
+    None
+}
+
+// Source Recordフィルタのリプレイバッファを保存する録画先
+pub struct SourceRecordClip {
+    obs: Arc<Obs>,
+    angle: SourceRecordAngle,
+    listener: Mutex<Option<Sender<PathBuf>>>,
+    // これまでに保存されたクリップ。保存中はロックしたままにして、
+    // 続けて届いたトリガーが同じファイルを自分のクリップと取り違えないようにする
+    claimed: tokio::sync::Mutex<HashSet<PathBuf>>,
+}
+
+impl SourceRecordClip {
+    pub fn new(obs: Arc<Obs>, angle: SourceRecordAngle) -> Self {
+        Self {
+            obs,
+            angle,
+            listener: Mutex::new(None),
+            claimed: tokio::sync::Mutex::new(HashSet::new()),
+        }
+    }
+
+    async fn call(&self, request_type: &str) -> Result<(), String> {
+        let mut data = json!({ "source": self.angle.source });
+        if let Some(filter) = &self.angle.filter {
+            data["filter"] = json!(filter);
+        }
+        self.obs
+            .call_vendor_request(VENDOR, request_type, &data)
+            .await
+            .map_err(|e| format!("{}: {}", self.angle.name, e))?;
+        Ok(())
+    }
+
+    // フィルタはイベントを送らないので、保存先に増えたファイルを保存されたクリップとする
+    async fn save(&self) -> Result<(), String> {
+        let mut claimed = self.claimed.lock().await;
+        let mut known: HashSet<PathBuf> = list_videos(&self.angle.dir)
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect();
+        known.extend(claimed.iter().cloned());
+        self.call("replay_buffer_save").await?;
+        let path = wait_for_new_file(&self.angle.dir, &known, WAIT_TIMEOUT, POLL_INTERVAL)
+            .await
+            .ok_or_else(|| format!("{:?}にクリップが保存されませんでした", self.angle.dir))?;
+        claimed.insert(path.clone());
+        drop(claimed);
+        let tx = self.listener.lock().unwrap().clone();
+        if let Some(tx) = tx {
+            tx.send(path).await.map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+}
+
+impl ClipBackend for SourceRecordClip {
+    fn save_replay(&self) -> BackendFuture<'_, ()> {
+        Box::pin(self.save())
+    }
+
+    // 起動時にフィルタのリプレイバッファを動かしておく。動いていなければ保存しても何も書き出されない
+    fn listen_saved_clips(&self, tx: Sender<PathBuf>) -> BackendFuture<'_, ()> {
+        *self.listener.lock().unwrap() = Some(tx);
+        Box::pin(self.call("replay_buffer_start"))
+    }
+
+    fn list_saved_clips(&self) -> BackendFuture<'_, Vec<PathBuf>> {
+        let paths = list_videos(&self.angle.dir)
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect();
+        Box::pin(async { Ok(paths) })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_wait_for_new_file() {
+        let dir = std::env::temp_dir().join(format!("source_record_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("old.mkv"), b"old").unwrap();
+        std::fs::write(dir.join("notes.txt"), b"not a video").unwrap();
+        let before: HashSet<PathBuf> = list_videos(&dir).into_iter().map(|(p, _)| p).collect();
+        assert_eq!(before.len(), 1);
+
+        let writer_dir = dir.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            std::fs::write(writer_dir.join("Ball Cam.mkv"), b"new clip").unwrap();
+        });
+        let path = wait_for_new_file(
+            &dir,
+            &before,
+            Duration::from_secs(5),
+            Duration::from_millis(10),
+        )
+        .await;
+        assert_eq!(path, Some(dir.join("Ball Cam.mkv")));
+
+        // 新しいファイルが無ければタイムアウトする
+        let before: HashSet<PathBuf> = list_videos(&dir).into_iter().map(|(p, _)| p).collect();
+        let path = wait_for_new_file(
+            &dir,
+            &before,
+            Duration::from_millis(50),
+            Duration::from_millis(10),
+        )
+        .await;
+        assert_eq!(path, None);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_save_claims_each_file() {
+        let mock = crate::obs_mock::MockObs::start().await;
+        let mut obs = Obs::new();
+        obs.connect("127.0.0.1", mock.port, None).await.unwrap();
+        let dir = std::env::temp_dir().join(format!("source_record_claim_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let clip = SourceRecordClip::new(
+            Arc::new(obs),
+            SourceRecordAngle {
+                name: "ball_cam".to_string(),
+                obs: "main".to_string(),
+                source: "Ball Cam".to_string(),
+                filter: None,
+                dir: dir.clone(),
+            },
+        );
+        let (tx, mut rx) = tokio::sync::mpsc::channel(8);
+        clip.listen_saved_clips(tx).await.unwrap();
+        assert_eq!(
+            mock.requests_of("CallVendorRequest")[0]["requestType"],
+            "replay_buffer_start"
+        );
+
+        // 保存を頼まれるたびにフィルタが1ファイル書き出す
+        let writer_dir = dir.clone();
+        let state = mock.state.clone();
+        tokio::spawn(async move {
+            let mut written = 0;
+            while written < 2 {
+                let saves = state
+                    .lock()
+                    .unwrap()
+                    .requests
+                    .iter()
+                    .filter(|(_, d)| d["requestType"] == "replay_buffer_save")
+                    .count();
+                if saves > written {
+                    written += 1;
+                    let path = writer_dir.join(format!("Ball Cam {written}.mkv"));
+                    std::fs::write(path, b"clip").unwrap();
+                }
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        });
+
+        // 続けて届いたトリガーは前の保存が終わってから頼み、別々のファイルを受け取る
+        let (first, second) = tokio::join!(clip.save(), clip.save());
+        first.unwrap();
+        second.unwrap();
+        let mut paths = vec![rx.recv().await.unwrap(), rx.recv().await.unwrap()];
+        paths.sort();
+        assert_eq!(
+            paths,
+            vec![dir.join("Ball Cam 1.mkv"), dir.join("Ball Cam 2.mkv")]
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,487 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::Result;
+use futures_util::StreamExt;
+use log::{debug, error, info, warn};
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use serde_json::{Value, json};
+use tokio::io::AsyncReadExt;
+use tokio::net::TcpStream;
+use tokio::sync::mpsc::Sender;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+use crate::mugi_capture::CaptureWriter;
+
+// ゲームが起動していないときに接続し直す間隔
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(3);
+
+// Rocket League公式のStats APIのメッセージ。DataはJSON文字列で届くこともある
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+struct Envelope {
+    event: String,
+    #[serde(default)]
+    data: Value,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "PascalCase")]
+struct StatsPlayer {
+    name: String,
+    team_num: u32,
+    #[serde(default)]
+    score: u32,
+    #[serde(default)]
+    goals: u32,
+    #[serde(default)]
+    shots: u32,
+    #[serde(default)]
+    assists: u32,
+    #[serde(default)]
+    saves: u32,
+    #[serde(default)]
+    touches: u32,
+    #[serde(default)]
+    demos: u32,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+struct StatsTeam {
+    name: String,
+    team_num: u32,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+struct StatsGame {
+    #[serde(default)]
+    teams: Vec<StatsTeam>,
+    time_seconds: Option<u32>,
+    #[serde(default, rename = "bOvertime", alias = "bOverTime")]
+    overtime: bool,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+struct UpdateState {
+    #[serde(default)]
+    players: Vec<StatsPlayer>,
+    game: Option<StatsGame>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+struct PlayerRef {
+    name: String,
+    team_num: u32,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+struct GoalScored {
+    scorer: PlayerRef,
+    assister: Option<PlayerRef>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+struct StatfeedEvent {
+    event_name: String,
+    main_target: Option<PlayerRef>,
+    secondary_target: Option<PlayerRef>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+struct ClockUpdated {
+    time_seconds: u32,
+    #[serde(default, rename = "bOvertime", alias = "bOverTime")]
+    overtime: bool,
+}
+
+fn mugi(cmd: &str, data: Value) -> String {
+    json!({ "cmd": cmd, "data": data }).to_string()
+}
+
+fn team_name(team_num: u32) -> &'static str {
+    if team_num == 0 { "blue" } else { "orange" }
+}
+
+// Stats APIのイベントをMugiのメッセージに変換する
+// UpdateStateは毎フレーム届くので、変化した値だけをメッセージにする
+#[derive(Debug, Default)]
+pub struct Translator {
+    match_guid: Option<String>,
+    team_names: Option<(String, String)>,
+    clock: Option<(u32, bool)>,
+    players: Vec<StatsPlayer>,
+}
+
+impl Translator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn translate(&mut self, msg: &Value) -> Result<Vec<String>> {
+        let envelope = Envelope::deserialize(msg)?;
+        let data = match envelope.data {
+            Value::String(s) => serde_json::from_str(&s)?,
+            data => data,
+        };
+        let mut out = Vec::new();
+        if let Some(guid) = data.get("MatchGuid").and_then(Value::as_str)
+            && !guid.is_empty()
+            && self.match_guid.as_deref() != Some(guid)
+        {
+            self.match_guid = Some(guid.to_string());
+            self.team_names = None;
+            self.players.clear();
+            out.push(mugi("matchId", json!({ "matchId": guid })));
+        }
+        match envelope.event.as_str() {
+            "UpdateState" => self.update_state(parse(data)?, &mut out),
+            "ClockUpdatedSeconds" => {
+                let clock: ClockUpdated = parse(data)?;
+                self.update_clock(clock.time_seconds, clock.overtime, &mut out);
+            }
+            "MatchInitialized" => out.push(mugi("start", Value::Null)),
+            "GoalScored" => {
+                let goal: GoalScored = parse(data)?;
+                out.push(mugi("scored", Value::Null));
+                out.push(mugi(
+                    "goals",
+                    json!({
+                        "team": team_name(goal.scorer.team_num),
+                        "scoreId": goal.scorer.name,
+                        "assistId": goal.assister.map(|a| a.name).unwrap_or_default(),
+                    }),
+                ));
+            }
+            "StatfeedEvent" => {
+                let event: StatfeedEvent = parse(data)?;
+                match event.event_name.as_str() {
+                    "EpicSave" => out.push(mugi("epicSave", Value::Null)),
+                    "Demolish" => {
+                        let index = |p: Option<PlayerRef>| {
+                            p.and_then(|p| self.players.iter().position(|s| s.name == p.name))
+                        };
+                        if let (Some(receiver), Some(victim)) =
+                            (index(event.main_target), index(event.secondary_target))
+                        {
+                            out.push(mugi(
+                                "demolished",
+                                json!({ "receiverIndex": receiver, "victimIndex": victim }),
+                            ));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            "GoalReplayEnd" => out.push(mugi("endReplay", Value::Null)),
+            "MatchEnded" => out.push(mugi("end", Value::Null)),
+            "PodiumStart" => out.push(mugi("endStats", Value::Null)),
+            "MatchDestroyed" => *self = Self::default(),
+            _ => {}
+        }
+        Ok(out)
+    }
+
+    fn update_state(&mut self, state: UpdateState, out: &mut Vec<String>) {
+        if let Some(game) = &state.game {
+            let name = |num| {
+                game.teams
+                    .iter()
+                    .find(|t| t.team_num == num)
+                    .map(|t| t.name.clone())
+                    .unwrap_or_default()
+            };
+            let names = (name(0), name(1));
+            if let Some(guid) = &self.match_guid
+                && self.team_names.as_ref() != Some(&names)
+            {
+                out.push(mugi(
+                    "teamNames",
+                    json!({ "blue": names.0, "orange": names.1, "matchId": guid }),
+                ));
+                self.team_names = Some(names);
+            }
+            if let Some(time) = game.time_seconds {
+                self.update_clock(time, game.overtime, out);
+            }
+        }
+        if state.players.is_empty() || state.players == self.players {
+            return;
+        }
+        let names: Vec<&str> = state.players.iter().map(|p| p.name.as_str()).collect();
+        if names
+            != self
+                .players
+                .iter()
+                .map(|p| p.name.as_str())
+                .collect::<Vec<_>>()
+        {
+            out.push(mugi("playerTable", json!(names)));
+        }
+        let stats: Vec<Value> = state
+            .players
+            .iter()
+            .map(|p| {
+                json!({
+                    "id": p.name,
+                    "teams": p.team_num,
+                    "scores": p.score,
+                    "goals": p.goals,
+                    "assists": p.assists,
+                    "saves": p.saves,
+                    "shots": p.shots,
+                    "demos": p.demos,
+                    "ballTouches": p.touches,
+                })
+            })
+            .collect();
+        out.push(mugi("stats", json!(stats)));
+        self.players = state.players;
+    }
+
+    fn update_clock(&mut self, time: u32, overtime: bool, out: &mut Vec<String>) {
+        if self.clock == Some((time, overtime)) {
+            return;
+        }
+        self.clock = Some((time, overtime));
+        out.push(mugi(
+            "time",
+            json!({ "time": time, "isOvertime": u8::from(overtime) }),
+        ));
+    }
+}
+
+fn parse<T: DeserializeOwned>(data: Value) -> Result<T> {
+    Ok(serde_json::from_value(data)?)
+}
+
+// 受信したバイト列から読み終わったJSONを取り出す。続きが届いていない分はbufに残す
+fn drain_messages(buf: &mut Vec<u8>) -> Vec<Value> {
+    let mut messages = Vec::new();
+    let mut stream = serde_json::Deserializer::from_slice(buf).into_iter::<Value>();
+    let consumed = loop {
+        match stream.next() {
+            Some(Ok(msg)) => messages.push(msg),
+            Some(Err(e)) if e.is_eof() => break stream.byte_offset(),
+            Some(Err(e)) => {
+                // 読み直せないので受信済みの分は捨てる
+                warn!("Invalid Stats API message: {}", e);
+                break buf.len();
+            }
+            None => break buf.len(),
+        }
+    };
+    buf.drain(..consumed);
+    messages
+}
+
+// Stats APIの接続。ws://で始まる接続先はWebSocket、それ以外は生のTCPで読む
+enum Connection {
+    Tcp(TcpStream),
+    WebSocket(Box<WebSocketStream<MaybeTlsStream<TcpStream>>>),
+}
+
+async fn connect(addr: &str) -> Result<Connection> {
+    if addr.starts_with("ws://") {
+        let (ws, _) = tokio_tungstenite::connect_async(addr).await?;
+        Ok(Connection::WebSocket(Box::new(ws)))
+    } else {
+        Ok(Connection::Tcp(TcpStream::connect(addr).await?))
+    }
+}
+
+impl Connection {
+    // 次に届いたバイト列を返す。切断されたらNone
+    async fn read(&mut self) -> Option<Vec<u8>> {
+        match self {
+            Self::Tcp(stream) => {
+                let mut chunk = [0; 8192];
+                match stream.read(&mut chunk).await {
+                    Ok(0) => None,
+                    Ok(n) => Some(chunk[..n].to_vec()),
+                    Err(e) => {
+                        warn!("Stats API connection error: {}", e);
+                        None
+                    }
+                }
+            }
+            Self::WebSocket(ws) => loop {
+                match ws.next().await? {
+                    Ok(Message::Text(text)) => return Some(text.as_bytes().to_vec()),
+                    Ok(Message::Binary(data)) => return Some(data.to_vec()),
+                    Ok(Message::Close(_)) => return None,
+                    Ok(_) => {}
+                    Err(e) => {
+                        warn!("Stats API connection error: {}", e);
+                        return None;
+                    }
+                }
+            },
+        }
+    }
+}
+
+// Stats APIに接続し続け、変換したMugiのメッセージをtxに送る
+pub async fn run(addr: String, tx: Sender<String>, capture: Arc<Mutex<Option<CaptureWriter>>>) {
+    loop {
+        let mut connection = match connect(&addr).await {
+            Ok(connection) => connection,
+            Err(e) => {
+                debug!("Stats API is not available at {}: {}", addr, e);
+                tokio::time::sleep(RECONNECT_INTERVAL).await;
+                continue;
+            }
+        };
+        info!("Connected to Stats API at {}", addr);
+        let mut translator = Translator::new();
+        let mut buf = Vec::new();
+        // WebSocketでも1フレームに複数のJSONが入ることがあるので、TCPと同じく区切りながら読む
+        while let Some(data) = connection.read().await {
+            buf.extend_from_slice(&data);
+            for msg in drain_messages(&mut buf) {
+                let converted = match translator.translate(&msg) {
+                    Ok(converted) => converted,
+                    Err(e) => {
+                        error!("Failed to translate Stats API message {}: {}", msg, e);
+                        continue;
+                    }
+                };
+                for d in converted {
+                    // キャプチャ中なら変換後のメッセージを書き出す
+                    if let Some(writer) = capture.lock().unwrap().as_mut()
+                        && let Err(e) = writer.write(&d)
+                    {
+                        error!("Failed to write capture: {}", e);
+                    }
+                    if tx.send(d).await.is_err() {
+                        return;
+                    }
+                }
+            }
+        }
+        info!("Stats API disconnected");
+        tokio::time::sleep(RECONNECT_INTERVAL).await;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mugi_schema::{self, MugiCmd};
+    use tokio::io::AsyncWriteExt;
+    use tokio::net::TcpListener;
+
+    const SESSION: &str = include_str!("../fixtures/stats_api/session.jsonl");
+
+    fn cmds(messages: &[String]) -> Vec<MugiCmd> {
+        messages
+            .iter()
+            .map(|m| mugi_schema::parse_cmd(m).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_translate_session() {
+        let mut translator = Translator::new();
+        let mut out = Vec::new();
+        for line in SESSION.lines() {
+            out.extend(
+                translator
+                    .translate(&serde_json::from_str(line).unwrap())
+                    .unwrap(),
+            );
+        }
+        assert_eq!(
+            cmds(&out),
+            vec![
+                MugiCmd::MatchId,
+                MugiCmd::Start,
+                MugiCmd::TeamNames,
+                MugiCmd::Time,
+                MugiCmd::PlayerTable,
+                MugiCmd::Stats,
+                MugiCmd::Time,
+                MugiCmd::Demolished,
+                MugiCmd::EpicSave,
+                MugiCmd::Scored,
+                MugiCmd::Goals,
+                MugiCmd::EndReplay,
+                MugiCmd::End,
+                MugiCmd::EndStats,
+            ]
+        );
+        let goal: mugi_schema::Goals = mugi_schema::parse_data(&out[10]).unwrap().unwrap();
+        assert_eq!(goal.team, "orange");
+        assert_eq!(goal.score_id, "Mugi_1");
+        assert_eq!(
+            out[7],
+            r#"{"cmd":"demolished","data":{"receiverIndex":1,"victimIndex":0}}"#
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_against_local_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            // 区切り無しで連結し、途中で分割して送る
+            let body: String = SESSION.lines().collect();
+            let (head, tail) = body.as_bytes().split_at(body.len() / 2);
+            socket.write_all(head).await.unwrap();
+            socket.flush().await.unwrap();
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            socket.write_all(tail).await.unwrap();
+        });
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(32);
+        let client = tokio::spawn(run(addr, tx, Arc::new(Mutex::new(None))));
+        let mut received = Vec::new();
+        while received.len() < 14 {
+            received.push(rx.recv().await.unwrap());
+        }
+        client.abort();
+        assert_eq!(cmds(&received)[0], MugiCmd::MatchId);
+        assert_eq!(cmds(&received)[13], MugiCmd::EndStats);
+    }
+
+    #[tokio::test]
+    async fn test_run_against_websocket_server() {
+        use futures_util::SinkExt;
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = format!("ws://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let mut ws = tokio_tungstenite::accept_async(socket).await.unwrap();
+            // 1フレームに1メッセージ。最後のフレームには2つまとめて入れる
+            let lines: Vec<&str> = SESSION.lines().collect();
+            let (last, rest) = lines.split_last().unwrap();
+            let (second_last, rest) = rest.split_last().unwrap();
+            for line in rest {
+                ws.send(Message::text(*line)).await.unwrap();
+            }
+            ws.send(Message::text(format!("{second_last}{last}")))
+                .await
+                .unwrap();
+        });
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(32);
+        let client = tokio::spawn(run(addr, tx, Arc::new(Mutex::new(None))));
+        let mut received = Vec::new();
+        while received.len() < 14 {
+            received.push(rx.recv().await.unwrap());
+        }
+        client.abort();
+        assert_eq!(cmds(&received)[0], MugiCmd::MatchId);
+        assert_eq!(cmds(&received)[13], MugiCmd::EndStats);
+    }
+}
@@ -1,18 +1,157 @@
-use log::info;
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
 use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex, RwLock};
 use tokio::net::UdpSocket;
+use tokio::sync::broadcast;
 use tokio::sync::mpsc::Sender;
+
+use crate::mugi_capture::CaptureWriter;
 // use tauri::async_runtime::{Receiver,Sender};
 
-pub async fn bind_socket(tx: Sender<String>) -> io::Result<()> {
-    let sock = UdpSocket::bind("0.0.0.0:12344").await?;
+pub const MUGI_PORT: u16 = 12344;
+
+// 受信したMugiのデータグラムを他のツールにも流す設定
+// targetsは"host:port"の形式
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct RelaySettings {
+    pub targets: Vec<String>,
+    // APIサーバーの/api/mugiにも流す
+    pub websocket: bool,
+}
+
+// 名前解決済みの転送先
+#[derive(Debug, Clone, Default)]
+pub struct Relay {
+    pub targets: Vec<SocketAddr>,
+    pub websocket: bool,
+}
+
+impl Relay {
+    pub async fn resolve(settings: &RelaySettings) -> Result<Self, String> {
+        let mut targets = Vec::new();
+        for target in &settings.targets {
+            let addrs = tokio::net::lookup_host(target.trim())
+                .await
+                .map_err(|e| format!("転送先{}を解決できません: {}", target, e))?;
+            let addr = addrs
+                .into_iter()
+                .find(|a| a.is_ipv4())
+                .ok_or_else(|| format!("転送先{}を解決できません", target))?;
+            // 自分自身に送ると無限に転送し続ける
+            if addr.port() == MUGI_PORT && is_local_ip(addr.ip()) {
+                return Err(format!("転送先{}は受信ポートと同じです", target));
+            }
+            targets.push(addr);
+        }
+        Ok(Self {
+            targets,
+            websocket: settings.websocket,
+        })
+    }
+}
+
+// このマシンのアドレスか。自分に割り当てられたアドレスにしかbindできないことを使って調べる
+fn is_local_ip(ip: IpAddr) -> bool {
+    ip.is_loopback() || ip.is_unspecified() || std::net::UdpSocket::bind((ip, 0)).is_ok()
+}
+
+// 受け取ったままのバイト列を転送する。届かない転送先があっても他には送る
+pub async fn forward(sock: &UdpSocket, targets: &[SocketAddr], data: &[u8]) {
+    for target in targets {
+        if let Err(e) = sock.send_to(data, target).await {
+            warn!("Failed to relay Mugi packet to {}: {}", target, e);
+        }
+    }
+}
+
+pub async fn bind_socket(
+    tx: Sender<String>,
+    capture: Arc<Mutex<Option<CaptureWriter>>>,
+    relay: Arc<RwLock<Relay>>,
+    raw: broadcast::Sender<String>,
+) -> io::Result<()> {
+    let sock = UdpSocket::bind(("0.0.0.0", MUGI_PORT)).await?;
     info!("Listening on {}", sock.local_addr()?);
-    let mut buf = [0; 1024];
-    // let mut f = File::create("mugi_log.txt").await?;
+    // UDPの最大サイズ。大きなstatsが途中で切れないようにする
+    let mut buf = [0; 65535];
     loop {
-        let (size, _addr) = sock.recv_from(&mut buf).await?;
-        let data = std::str::from_utf8(&buf[..size]).unwrap();
-        let d = data.to_string();
-        tx.send(d).await.unwrap();
+        let (size, _addr) = match sock.recv_from(&mut buf).await {
+            Ok(received) => received,
+            // Windowsでは転送先が閉じているとICMPの応答で受信がエラーになる
+            Err(e) if e.kind() == io::ErrorKind::ConnectionReset => continue,
+            Err(e) => return Err(e),
+        };
+        let relay = relay.read().unwrap().clone();
+        forward(&sock, &relay.targets, &buf[..size]).await;
+        let d = match std::str::from_utf8(&buf[..size]) {
+            Ok(data) => data.to_string(),
+            Err(e) => {
+                error!("Received non UTF-8 packet: {}", e);
+                continue;
+            }
+        };
+        if relay.websocket {
+            // 接続中のクライアントがいなければ捨てる
+            let _ = raw.send(d.clone());
+        }
+        // キャプチャ中なら受信したまま書き出す
+        if let Some(writer) = capture.lock().unwrap().as_mut()
+            && let Err(e) = writer.write(&d)
+        {
+            error!("Failed to write capture: {}", e);
+        }
+        if tx.send(d).await.is_err() {
+            error!("Mugi receiver closed");
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_relay() {
+        let settings = RelaySettings {
+            targets: vec!["127.0.0.1:12344".to_string()],
+            websocket: false,
+        };
+        assert!(Relay::resolve(&settings).await.is_err());
+
+        // LANのアドレスでも自分自身なら拒否する
+        let lan = std::net::UdpSocket::bind("0.0.0.0:0").unwrap();
+        if lan.connect("198.51.100.1:9").is_ok() {
+            let ip = lan.local_addr().unwrap().ip();
+            let settings = RelaySettings {
+                targets: vec![SocketAddr::new(ip, MUGI_PORT).to_string()],
+                websocket: false,
+            };
+            assert!(Relay::resolve(&settings).await.is_err(), "{ip}");
+        }
+        // 他のマシンの同じポートには転送できる
+        let settings = RelaySettings {
+            targets: vec!["198.51.100.1:12344".to_string()],
+            websocket: false,
+        };
+        assert!(Relay::resolve(&settings).await.is_ok());
+
+        let downstream = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let settings = RelaySettings {
+            targets: vec![downstream.local_addr().unwrap().to_string()],
+            websocket: true,
+        };
+        let relay = Relay::resolve(&settings).await.unwrap();
+        assert!(relay.websocket);
+
+        let sock = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let packet = br#"{"cmd":"clock","data":{"clock":300}}"#;
+        forward(&sock, &relay.targets, packet).await;
+        let mut buf = [0; 1024];
+        let (size, _) = downstream.recv_from(&mut buf).await.unwrap();
+        assert_eq!(&buf[..size], packet);
     }
 }
@@ -1,26 +1,225 @@
-use std::path::PathBuf;
+use std::sync::Mutex;
 
 use log::{error, info};
-use tauri::Emitter;
+use tauri::{AppHandle, Emitter, Manager, Runtime};
 use tokio::sync::mpsc::Receiver;
 
+use crate::AppState;
+use crate::api_server;
+use crate::backend::SavedClip;
+use crate::clip_catalog::{Clip, ClipCatalog};
+use crate::clip_export::ExportSettings;
+use crate::clip_naming::{self, RenameSettings};
+use crate::clip_storage::{self, StorageSettings};
+use crate::media_probe;
+
 pub struct VlcManager {}
 
 impl VlcManager {
     pub fn new() -> Self {
         Self {}
     }
-    // replay_bufferのpathをフロントエンドに送信
-    // rx: OBSのreplay_bufferのpathが降ってくる
-    pub fn set_event_listener(&self, mut rx: Receiver<PathBuf>, app_handle: tauri::AppHandle) {
+    // replay_bufferのpathをカタログに登録してフロントエンドに送信
+    // rx: 各録画先で保存されたクリップのpathが降ってくる
+    pub fn set_event_listener<R: Runtime>(
+        &self,
+        mut rx: Receiver<SavedClip>,
+        state: AppState,
+        app_handle: AppHandle<R>,
+    ) {
         tokio::spawn(async move {
-            while let Some(path) = rx.recv().await {
-                info!("path:{:?}", path);
-                // フロントエンドに個別のパスを送信
-                if let Err(e) = app_handle.emit("video_path_added", path) {
+            while let Some(saved) = rx.recv().await {
+                info!("path:{:?} ({})", saved.path, saved.angle);
+                let catalog = &state.clip_catalog;
+                let clip = catalog
+                    .lock()
+                    .unwrap()
+                    .attach_angle(&saved.angle, saved.path);
+                info!("clip {} saved as {:?}", clip.id, clip.context.kind);
+                let rename = state.rename_settings.read().unwrap().clone();
+                let storage = state.storage_settings.read().unwrap().clone();
+                let series = state
+                    .series
+                    .lock()
+                    .unwrap()
+                    .current()
+                    .map(|s| s.name.clone());
+                let clip = Self::organise_clip(clip, catalog, &rename, &storage, series.as_deref());
+                api_server::publish(&state.api_events, "clip_saved", &clip);
+                // フロントエンドに個別のクリップ情報を送信
+                if let Err(e) = app_handle.emit("video_path_added", &clip) {
                     error!("Failed to emit video_path_added event: {}", e);
                 }
+                // ffprobe/ffmpegを待たずに次のクリップを受け付け、長さとサムネイルは後からclip_updatedで送る
+                let settings = state.export_settings.read().unwrap().clone();
+                let state = state.clone();
+                let app_handle = app_handle.clone();
+                tokio::spawn(async move {
+                    let catalog = &state.clip_catalog;
+                    let Some(clip) = Self::probe_clip(clip, catalog, &settings, &app_handle).await
+                    else {
+                        return;
+                    };
+                    api_server::publish(&state.api_events, "clip_updated", &clip);
+                    if let Err(e) = app_handle.emit("clip_updated", &clip) {
+                        error!("Failed to emit clip_updated event: {}", e);
+                    }
+                });
             }
         });
     }
+
+    // 保存フォルダの整理とテンプレートに従ったリネーム
+    // どちらも無効ならOBSが保存したまま
+    fn organise_clip(
+        clip: Clip,
+        catalog: &Mutex<ClipCatalog>,
+        rename: &RenameSettings,
+        storage: &StorageSettings,
+        series: Option<&str>,
+    ) -> Clip {
+        let Some(obs_dir) = clip.path.parent() else {
+            return clip;
+        };
+        let dir = storage
+            .game_dir(&clip, series)
+            .unwrap_or_else(|| obs_dir.to_path_buf());
+        let target = if rename.enabled {
+            dir.join(clip_naming::render_template(&rename.template, &clip))
+        } else {
+            match clip.path.file_name() {
+                Some(name) => dir.join(name),
+                None => return clip,
+            }
+        };
+        match clip_storage::move_clip(&clip, target) {
+            Ok(path) if path != clip.path => {
+                info!("clip {} moved to {:?}", clip.id, path);
+                catalog
+                    .lock()
+                    .unwrap()
+                    .set_path(clip.id, path)
+                    .unwrap_or(clip)
+            }
+            Ok(_) => clip,
+            Err(e) => {
+                error!("Failed to move clip {}: {}", clip.id, e);
+                clip
+            }
+        }
+    }
+
+    // 長さ・解像度・コーデックを調べてサムネイルを作り、更新したクリップを返す
+    // ffprobe/ffmpegが無い場合や、その間にクリップが削除された場合はNone
+    async fn probe_clip<R: Runtime>(
+        clip: Clip,
+        catalog: &Mutex<ClipCatalog>,
+        settings: &ExportSettings,
+        app_handle: &AppHandle<R>,
+    ) -> Option<Clip> {
+        let media = match media_probe::probe(&settings.ffprobe_path(), &clip.path).await {
+            Ok(media) => media,
+            Err(e) => {
+                error!("Failed to probe {:?}: {}", clip.path, e);
+                return None;
+            }
+        };
+
+        let thumbnail = match app_handle.path().app_cache_dir() {
+            Ok(dir) => {
+                let dir = dir.join("thumbnails");
+                let output = dir.join(media_probe::thumbnail_name(&clip.path));
+                let at_sec = media.duration_sec.unwrap_or(0.0) / 2.0;
+                let res = match std::fs::create_dir_all(&dir) {
+                    Ok(()) => {
+                        media_probe::generate_thumbnail(
+                            &settings.ffmpeg_path,
+                            &clip.path,
+                            at_sec,
+                            &output,
+                        )
+                        .await
+                    }
+                    Err(e) => Err(e.to_string()),
+                };
+                match res {
+                    Ok(()) => Some(output),
+                    Err(e) => {
+                        error!("Failed to generate thumbnail for {:?}: {}", clip.path, e);
+                        None
+                    }
+                }
+            }
+            Err(e) => {
+                error!("Failed to get cache dir: {}", e);
+                None
+            }
+        };
+
+        catalog.lock().unwrap().set_media(clip.id, media, thumbnail)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::clip_catalog::{ClipContext, ClipKind};
+    use crate::match_state::MatchState;
+    use crate::mugi_schema::Goals;
+    use std::path::PathBuf;
+
+    // 既定のテンプレートと試合フォルダを組み合わせても、試合IDの階層が二重にならない
+    #[test]
+    fn test_organise_clip_with_default_template() {
+        let root = std::env::temp_dir().join(format!("vlc_manager_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let obs_dir = root.join("obs");
+        std::fs::create_dir_all(&obs_dir).unwrap();
+        let source = obs_dir.join("Replay 1.mkv");
+        std::fs::write(&source, b"clip").unwrap();
+
+        let state = MatchState {
+            match_id: Some("ABC".to_string()),
+            game_number: 2,
+            clock: Some(65),
+            last_goal: Some(Goals {
+                team: "blue".to_string(),
+                score_id: "Player_1".to_string(),
+                assist_id: String::new(),
+            }),
+            ..MatchState::default()
+        };
+        let catalog = Mutex::new(ClipCatalog::new());
+        let clip = {
+            let mut catalog = catalog.lock().unwrap();
+            catalog.push_pending(ClipContext::from_state(ClipKind::Goal, &state));
+            catalog.attach(source.clone())
+        };
+        let rename = RenameSettings {
+            enabled: true,
+            ..RenameSettings::default()
+        };
+        let storage = StorageSettings {
+            root: Some(root.join("clips")),
+            tournament: "Cup".to_string(),
+            series: String::new(),
+        };
+
+        let clip = VlcManager::organise_clip(clip, &catalog, &rename, &storage, Some("A vs B"));
+        let expected: PathBuf = [
+            root.as_path(),
+            "clips".as_ref(),
+            "Cup".as_ref(),
+            "A vs B".as_ref(),
+            "G2_ABC".as_ref(),
+            "G2_1-05_blue_Player_1_goal.mkv".as_ref(),
+        ]
+        .iter()
+        .collect();
+        assert_eq!(clip.path, expected);
+        assert!(expected.exists());
+        assert!(!source.exists());
+        assert_eq!(catalog.lock().unwrap().get(clip.id).unwrap().path, expected);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
 }
@@ -0,0 +1,400 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::broadcast;
+
+use crate::api_server::{ApiEvent, ApiEvents};
+
+// 1回の送信を待つ時間
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+// 送信履歴に残す件数
+const MAX_LOG_ENTRIES: usize = 200;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum WebhookEvent {
+    ClipSaved,
+    PlaybackStarted,
+    MatchEnded,
+}
+
+impl WebhookEvent {
+    // APIのイベント名から変換する。Webhookで送らないイベントはNone
+    fn from_api_event(name: &str) -> Option<Self> {
+        match name {
+            "clip_saved" => Some(Self::ClipSaved),
+            "playback_started" => Some(Self::PlaybackStarted),
+            "match_ended" => Some(Self::MatchEnded),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookTarget {
+    pub url: String,
+    // 空なら全てのイベントを送る
+    #[serde(default)]
+    pub events: Vec<WebhookEvent>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookSettings {
+    pub enabled: bool,
+    pub targets: Vec<WebhookTarget>,
+    // 失敗したときに送り直す回数を含めた最大の送信回数
+    pub max_attempts: u32,
+    // 最初の再送までの待ち時間。再送のたびに倍にする
+    pub retry_delay_ms: u64,
+}
+
+impl Default for WebhookSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            targets: Vec::new(),
+            max_attempts: 3,
+            retry_delay_ms: 1000,
+        }
+    }
+}
+
+// POSTするJSON
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookPayload {
+    pub event: WebhookEvent,
+    // UNIX時間(秒)
+    pub timestamp: u64,
+    pub path: Option<String>,
+    pub match_id: Option<String>,
+    pub scorer: Option<String>,
+    pub kind: Option<String>,
+    // イベントの内容そのもの(クリップ、再生したパスとクリップ、試合状況)
+    pub data: Value,
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+fn string_at(value: &Value, pointer: &str) -> Option<String> {
+    value.pointer(pointer)?.as_str().map(str::to_string)
+}
+
+impl WebhookPayload {
+    pub fn from_api_event(event: &ApiEvent) -> Option<Self> {
+        let kind = WebhookEvent::from_api_event(&event.event)?;
+        let data = &event.payload;
+        let payload = match kind {
+            WebhookEvent::ClipSaved => Self {
+                event: kind,
+                timestamp: data["savedAt"].as_u64().unwrap_or_else(now_secs),
+                path: string_at(data, "/path"),
+                match_id: string_at(data, "/context/matchId"),
+                scorer: string_at(data, "/context/scorer"),
+                kind: string_at(data, "/context/kind"),
+                data: data.clone(),
+            },
+            // 最初に再生したクリップの情報を入れる
+            WebhookEvent::PlaybackStarted => Self {
+                event: kind,
+                timestamp: now_secs(),
+                path: string_at(data, "/paths/0"),
+                match_id: string_at(data, "/clips/0/context/matchId"),
+                scorer: string_at(data, "/clips/0/context/scorer"),
+                kind: string_at(data, "/clips/0/context/kind"),
+                data: data.clone(),
+            },
+            // 試合全体のイベントなのでクリップの項目は空。最後のゴールなどはdataの試合状況にある
+            WebhookEvent::MatchEnded => Self {
+                event: kind,
+                timestamp: now_secs(),
+                path: None,
+                match_id: string_at(data, "/matchId"),
+                scorer: None,
+                kind: None,
+                data: data.clone(),
+            },
+        };
+        Some(payload)
+    }
+}
+
+// 送信結果の履歴
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookDelivery {
+    pub url: String,
+    pub event: WebhookEvent,
+    pub timestamp: u64,
+    pub attempts: u32,
+    pub delivered: bool,
+    // 最後の応答のステータスコード
+    pub status: Option<u16>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct WebhookLog {
+    entries: VecDeque<WebhookDelivery>,
+}
+
+impl WebhookLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn push(&mut self, delivery: WebhookDelivery) {
+        if self.entries.len() >= MAX_LOG_ENTRIES {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(delivery);
+    }
+
+    // 新しい順に返す
+    pub fn entries(&self) -> Vec<WebhookDelivery> {
+        self.entries.iter().rev().cloned().collect()
+    }
+}
+
+// 1つの送信先に送る。2xx以外は待ち時間を倍にしながら送り直す
+async fn deliver(
+    client: &reqwest::Client,
+    url: &str,
+    payload: &WebhookPayload,
+    settings: &WebhookSettings,
+) -> WebhookDelivery {
+    let mut delivery = WebhookDelivery {
+        url: url.to_string(),
+        event: payload.event,
+        timestamp: payload.timestamp,
+        attempts: 0,
+        delivered: false,
+        status: None,
+        error: None,
+    };
+    let mut delay = Duration::from_millis(settings.retry_delay_ms);
+    while delivery.attempts < settings.max_attempts.max(1) {
+        if delivery.attempts > 0 {
+            tokio::time::sleep(delay).await;
+            delay *= 2;
+        }
+        delivery.attempts += 1;
+        match client
+            .post(url)
+            .timeout(REQUEST_TIMEOUT)
+            .json(payload)
+            .send()
+            .await
+        {
+            Ok(res) => {
+                delivery.status = Some(res.status().as_u16());
+                if res.status().is_success() {
+                    delivery.delivered = true;
+                    delivery.error = None;
+                    return delivery;
+                }
+                delivery.error = Some(format!("HTTP {}", res.status()));
+            }
+            Err(e) => delivery.error = Some(e.to_string()),
+        }
+    }
+    delivery
+}
+
+// APIのイベントを購読し、設定された送信先にWebhookを送る
+pub fn spawn_webhooks(
+    events: &ApiEvents,
+    settings: Arc<RwLock<WebhookSettings>>,
+    log: Arc<Mutex<WebhookLog>>,
+) {
+    let mut rx = events.subscribe();
+    let client = reqwest::Client::new();
+    tokio::spawn(async move {
+        loop {
+            let event = match rx.recv().await {
+                Ok(event) => event,
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    warn!("Webhook dispatcher skipped {} events", n);
+                    continue;
+                }
+                Err(broadcast::error::RecvError::Closed) => return,
+            };
+            let Some(payload) = WebhookPayload::from_api_event(&event) else {
+                continue;
+            };
+            let settings = settings.read().unwrap().clone();
+            if !settings.enabled {
+                continue;
+            }
+            for target in &settings.targets {
+                if !target.events.is_empty() && !target.events.contains(&payload.event) {
+                    continue;
+                }
+                let client = client.clone();
+                let url = target.url.clone();
+                let payload = payload.clone();
+                let settings = settings.clone();
+                let log = log.clone();
+                tokio::spawn(async move {
+                    let delivery = deliver(&client, &url, &payload, &settings).await;
+                    if delivery.delivered {
+                        info!("Webhook {:?} delivered to {}", payload.event, url);
+                    } else {
+                        error!(
+                            "Webhook {:?} to {} failed after {} attempts: {:?}",
+                            payload.event, url, delivery.attempts, delivery.error
+                        );
+                    }
+                    log.lock().unwrap().push(delivery);
+                });
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::api_server;
+    use serde_json::json;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    // 最初の1回は500を返し、受け取ったボディを送るHTTPサーバー
+    async fn stub_server() -> (String, tokio::sync::mpsc::Receiver<Value>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let (tx, rx) = tokio::sync::mpsc::channel(8);
+        tokio::spawn(async move {
+            for attempt in 0.. {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut buf = Vec::new();
+                let mut chunk = [0; 4096];
+                let body = loop {
+                    let n = socket.read(&mut chunk).await.unwrap();
+                    buf.extend_from_slice(&chunk[..n]);
+                    let text = String::from_utf8_lossy(&buf).to_string();
+                    if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                        let len: usize = head
+                            .lines()
+                            .find_map(|l| {
+                                l.to_ascii_lowercase()
+                                    .strip_prefix("content-length:")
+                                    .map(|v| v.trim().parse().unwrap())
+                            })
+                            .unwrap_or(0);
+                        if body.len() >= len {
+                            break body.to_string();
+                        }
+                    }
+                };
+                let status = if attempt == 0 {
+                    "500 Internal Server Error"
+                } else {
+                    "200 OK"
+                };
+                let res =
+                    format!("HTTP/1.1 {status}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n");
+                socket.write_all(res.as_bytes()).await.unwrap();
+                tx.send(serde_json::from_str(&body).unwrap()).await.unwrap();
+            }
+        });
+        (url, rx)
+    }
+
+    #[tokio::test]
+    async fn test_clip_saved_webhook_retries() {
+        let (url, mut bodies) = stub_server().await;
+        let events: ApiEvents = broadcast::channel(8).0;
+        let settings = Arc::new(RwLock::new(WebhookSettings {
+            enabled: true,
+            targets: vec![
+                WebhookTarget {
+                    url: url.clone(),
+                    events: vec![WebhookEvent::ClipSaved],
+                },
+                // 再生のイベントだけを受ける送信先には送らない
+                WebhookTarget {
+                    url: "http://127.0.0.1:1/unused".to_string(),
+                    events: vec![WebhookEvent::PlaybackStarted],
+                },
+            ],
+            max_attempts: 3,
+            retry_delay_ms: 10,
+        }));
+        let log = Arc::new(Mutex::new(WebhookLog::new()));
+        spawn_webhooks(&events, settings, log.clone());
+
+        let clip = json!({
+            "id": 1,
+            "path": "C:/clips/goal.mkv",
+            "savedAt": 1700000000,
+            "context": { "kind": "goal", "matchId": "A", "scorer": "Moca_1" }
+        });
+        api_server::publish(&events, "clip_saved", &clip);
+
+        let first = bodies.recv().await.unwrap();
+        let second = bodies.recv().await.unwrap();
+        assert_eq!(first, second);
+        assert_eq!(second["event"], "clipSaved");
+        assert_eq!(second["path"], "C:/clips/goal.mkv");
+        assert_eq!(second["matchId"], "A");
+        assert_eq!(second["scorer"], "Moca_1");
+        assert_eq!(second["kind"], "goal");
+        assert_eq!(second["timestamp"], 1700000000);
+
+        // 履歴は送信を終えてから書かれる
+        for _ in 0..50 {
+            if !log.lock().unwrap().entries().is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let entries = log.lock().unwrap().entries();
+        assert_eq!(entries.len(), 1);
+        assert!(entries[0].delivered);
+        assert_eq!(entries[0].attempts, 2);
+        assert_eq!(entries[0].status, Some(200));
+    }
+
+    #[test]
+    fn test_playback_started_payload() {
+        let event = ApiEvent {
+            event: "playback_started".to_string(),
+            payload: json!({
+                "paths": ["C:/clips/goal.mkv", "C:/clips/save.mkv"],
+                "clips": [{
+                    "id": 1,
+                    "path": "C:/clips/goal.mkv",
+                    "context": { "kind": "goal", "matchId": "A", "scorer": "Moca_1" }
+                }]
+            }),
+        };
+        let payload = WebhookPayload::from_api_event(&event).unwrap();
+        assert_eq!(payload.event, WebhookEvent::PlaybackStarted);
+        assert_eq!(payload.path.as_deref(), Some("C:/clips/goal.mkv"));
+        assert_eq!(payload.match_id.as_deref(), Some("A"));
+        assert_eq!(payload.scorer.as_deref(), Some("Moca_1"));
+        assert_eq!(payload.kind.as_deref(), Some("goal"));
+
+        // 試合終了では最後のゴールを決めた選手をscorerにしない
+        let event = ApiEvent {
+            event: "match_ended".to_string(),
+            payload: json!({ "matchId": "A", "lastGoal": { "scoreId": "Moca_1" } }),
+        };
+        let payload = WebhookPayload::from_api_event(&event).unwrap();
+        assert_eq!(payload.match_id.as_deref(), Some("A"));
+        assert_eq!(payload.scorer, None);
+    }
+}
@@ -6,6 +6,14 @@ interface DashboardProps {
   version: string;
 }
 
+// video_path_addedで届くクリップ情報。長さとサムネイルは調べ終わった後のclip_updatedで届く
+interface SavedClip {
+  id: number;
+  path: string;
+  durationSec: number | null;
+  thumbnail: string | null;
+}
+
 function Dashboard({ version }: DashboardProps) {
   const [videoPaths, setVideoPaths] = useState<Set<string>>(new Set());
   const [sleepDuration, setSleepDuration] = useState<number>(0);
@@ -22,9 +30,9 @@ function Dashboard({ version }: DashboardProps) {
       }
       
       // イベントリスナー設定
-      const unlisten = await listen<string>("video_path_added", (event) => {
+      const unlisten = await listen<SavedClip>("video_path_added", (event) => {
         console.log("新しい動画パス受信:", event.payload);
-        setVideoPaths(prev => new Set([...prev, event.payload]));
+        setVideoPaths(prev => new Set([...prev, event.payload.path]));
       });
       
       return unlisten;